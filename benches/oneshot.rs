@@ -1,6 +1,9 @@
-use async_oneshot::oneshot;
+use async_oneshot::{oneshot, Receiver, Sender};
 use criterion::*;
 use futures::executor::block_on;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Instant;
 
 pub fn create_destroy(c: &mut Criterion) {
     c.bench_function("create_destroy", |b| b.iter(oneshot::<usize>));
@@ -112,5 +115,190 @@ pub fn wait(c: &mut Criterion) {
     });
 }
 
-criterion_group!(benches, create_destroy, send, try_recv, recv, wait);
+pub fn compare(c: &mut Criterion) {
+    let mut group = c.benchmark_group("compare.send_recv");
+    group.bench_function("async_oneshot", |b| {
+        b.iter(|| {
+            let (mut send, recv) = oneshot::<usize>();
+            send.send(1).unwrap();
+            block_on(recv).unwrap()
+        })
+    });
+    group.bench_function("futures", |b| {
+        b.iter(|| {
+            let (send, recv) = futures::channel::oneshot::channel::<usize>();
+            send.send(1).unwrap();
+            block_on(recv).unwrap()
+        })
+    });
+    group.bench_function("tokio", |b| {
+        b.iter(|| {
+            let (send, recv) = tokio::sync::oneshot::channel::<usize>();
+            send.send(1).unwrap();
+            block_on(recv).unwrap()
+        })
+    });
+    group.bench_function("oneshot", |b| {
+        b.iter(|| {
+            let (send, recv) = oneshot::channel::<usize>();
+            send.send(1).unwrap();
+            block_on(recv).unwrap()
+        })
+    });
+    // kanal has no oneshot, so use its smallest bounded channel.
+    group.bench_function("kanal", |b| {
+        b.iter(|| {
+            let (send, recv) = kanal::bounded_async::<usize>(1);
+            send.try_send(1).unwrap();
+            block_on(recv.recv()).unwrap()
+        })
+    });
+    group.finish();
+
+    let mut group = c.benchmark_group("compare.ping_pong");
+    macro_rules! ping_pong {
+        ($name:literal, $channel:expr, |$send:ident, $value:ident| $do_send:expr, |$recv:ident| $do_recv:expr) => {
+            group.bench_function($name, |b| {
+                b.iter_custom(|iters| {
+                    let (pings, ping_recvs): (Vec<_>, Vec<_>) =
+                        (0..iters).map(|_| $channel).unzip();
+                    let (pongs, pong_recvs): (Vec<_>, Vec<_>) =
+                        (0..iters).map(|_| $channel).unzip();
+                    let worker = thread::spawn(move || {
+                        for ($recv, $send) in ping_recvs.into_iter().zip(pongs) {
+                            let $value = $do_recv;
+                            $do_send;
+                        }
+                    });
+                    let start = Instant::now();
+                    for ($send, $recv) in pings.into_iter().zip(pong_recvs) {
+                        let $value = 1;
+                        $do_send;
+                        $do_recv;
+                    }
+                    let elapsed = start.elapsed();
+                    worker.join().unwrap();
+                    elapsed
+                })
+            });
+        };
+    }
+    ping_pong!(
+        "async_oneshot",
+        oneshot::<usize>(),
+        |send, value| {
+            let mut send = send;
+            send.send(value).unwrap()
+        },
+        |recv| block_on(recv).unwrap()
+    );
+    ping_pong!(
+        "futures",
+        futures::channel::oneshot::channel::<usize>(),
+        |send, value| send.send(value).unwrap(),
+        |recv| block_on(recv).unwrap()
+    );
+    ping_pong!(
+        "tokio",
+        tokio::sync::oneshot::channel::<usize>(),
+        |send, value| send.send(value).unwrap(),
+        |recv| block_on(recv).unwrap()
+    );
+    ping_pong!(
+        "oneshot",
+        oneshot::channel::<usize>(),
+        |send, value| send.send(value).unwrap(),
+        |recv| block_on(recv).unwrap()
+    );
+    ping_pong!(
+        "kanal",
+        kanal::bounded_async::<usize>(1),
+        |send, value| send.try_send(value).unwrap(),
+        |recv| block_on(recv.recv()).unwrap()
+    );
+    group.finish();
+}
+
+pub fn threads(c: &mut Criterion) {
+    let mut group = c.benchmark_group("threads");
+    group.bench_function("ping_pong", |b| {
+        b.iter_custom(|iters| {
+            let (pings, ping_recvs): (Vec<_>, Vec<_>) =
+                (0..iters).map(|_| oneshot::<usize>()).unzip();
+            let (pongs, pong_recvs): (Vec<_>, Vec<_>) =
+                (0..iters).map(|_| oneshot::<usize>()).unzip();
+            let worker = thread::spawn(move || {
+                for (recv, mut send) in ping_recvs.into_iter().zip(pongs) {
+                    send.send(block_on(recv).unwrap()).unwrap();
+                }
+            });
+            let start = Instant::now();
+            for (mut send, recv) in pings.into_iter().zip(pong_recvs) {
+                send.send(1).unwrap();
+                block_on(recv).unwrap();
+            }
+            let elapsed = start.elapsed();
+            worker.join().unwrap();
+            elapsed
+        })
+    });
+    group.bench_function("churn", |b| {
+        // A fixed pool of workers, each handed a batch of receivers per
+        // measurement, so only the channels are timed.
+        let workers: Vec<_> = (0..4)
+            .map(|_| {
+                let (jobs, job_recvs) = mpsc::channel::<(Vec<Receiver<usize>>, Sender<()>)>();
+                let handle = thread::spawn(move || {
+                    for (batch, mut done) in job_recvs {
+                        for recv in batch {
+                            block_on(recv).unwrap();
+                        }
+                        done.send(()).unwrap();
+                    }
+                });
+                (jobs, handle)
+            })
+            .collect();
+        b.iter_custom(|iters| {
+            let (sends, recvs): (Vec<_>, Vec<_>) = (0..iters).map(|_| oneshot::<usize>()).unzip();
+            let mut batches: Vec<Vec<_>> = workers.iter().map(|_| Vec::new()).collect();
+            for (i, recv) in recvs.into_iter().enumerate() {
+                batches[i % workers.len()].push(recv);
+            }
+            let dones: Vec<_> = workers
+                .iter()
+                .zip(batches)
+                .map(|((jobs, _), batch)| {
+                    let (done, done_recv) = oneshot();
+                    jobs.send((batch, done)).unwrap();
+                    done_recv
+                })
+                .collect();
+            let start = Instant::now();
+            for mut send in sends {
+                send.send(1).unwrap();
+            }
+            for done in dones {
+                block_on(done).unwrap();
+            }
+            start.elapsed()
+        });
+        for (jobs, handle) in workers {
+            drop(jobs);
+            handle.join().unwrap();
+        }
+    });
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    create_destroy,
+    send,
+    try_recv,
+    recv,
+    wait,
+    compare,
+    threads
+);
 criterion_main!(benches);
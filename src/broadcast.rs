@@ -0,0 +1,352 @@
+//! A oneshot channel whose receivers can be cloned, each receiving a
+//! clone of the single value sent.
+
+use crate::mutex::Mutex;
+use crate::waiters::{next_id, Waiters};
+use crate::Closed;
+use alloc::sync::Arc;
+use core::cell::UnsafeCell;
+use core::mem::MaybeUninit;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use core::task::{Context, Poll, Waker};
+use core::{fmt, future::Future, pin::Pin};
+
+const WAITERS_LOCKED_BIT: usize = 0;
+const WAITERS_PRESENT_BIT: usize = 1;
+const VALUE_PRESENT_BIT: usize = 2;
+const CLOSED_BIT: usize = 3;
+const SENDER_LOCKED_BIT: usize = 4;
+const SENDER_PRESENT_BIT: usize = 5;
+
+/// Create a new broadcast channel.
+///
+/// Clone the [`Receiver`] to add more recipients. Every receiver that
+/// is alive when the value is sent gets its own clone of it.
+pub fn broadcast<T: Clone>() -> (Sender<T>, Receiver<T>) {
+    let shared = Arc::new(Shared::new());
+    let sender = Sender {
+        shared: shared.clone(),
+        did_send: false,
+    };
+    let receiver = Receiver {
+        shared,
+        id: next_id(),
+        did_receive: false,
+    };
+    (sender, receiver)
+}
+
+struct Shared<T> {
+    // Carries the state of the waiter list and value.
+    state: AtomicUsize,
+
+    // Wakers of every receiver waiting for the value.
+    waiters: Waiters<WAITERS_PRESENT_BIT, WAITERS_LOCKED_BIT>,
+
+    // Value of the channel (present if VALUE_PRESENT_BIT is set). It is
+    // never modified once present, so receivers may share it.
+    value: UnsafeCell<MaybeUninit<T>>,
+
+    // Waker of the sender waiting for every receiver to finish.
+    sender: Mutex<Waker, SENDER_PRESENT_BIT, SENDER_LOCKED_BIT>,
+
+    // Number of live receivers.
+    receivers: AtomicUsize,
+
+    // Number of live receivers which have yet to return a result.
+    pending: AtomicUsize,
+}
+
+impl<T> Shared<T> {
+    fn new() -> Self {
+        Shared {
+            state: AtomicUsize::new(0),
+            waiters: Waiters::new(),
+            value: UnsafeCell::new(MaybeUninit::uninit()),
+            sender: Mutex::new(),
+            receivers: AtomicUsize::new(1),
+            pending: AtomicUsize::new(1),
+        }
+    }
+
+    /// Clones the value if present, or reports the channel closed.
+    fn try_clone(&self) -> Option<Result<T, Closed>>
+    where
+        T: Clone,
+    {
+        let state = self.state.load(Ordering::Acquire);
+        if state & (1 << VALUE_PRESENT_BIT) != 0 {
+            // SAFETY: The value is present and will not change until drop.
+            Some(Ok(unsafe { (*self.value.get()).assume_init_ref() }.clone()))
+        } else if state & (1 << CLOSED_BIT) != 0 {
+            Some(Err(Closed()))
+        } else {
+            None
+        }
+    }
+
+    /// Records that a receiver has returned a result or dropped without
+    /// one, waking the sender if it was the last.
+    fn finish_receiver(&self) {
+        if self.pending.fetch_sub(1, Ordering::AcqRel) != 1 {
+            return;
+        }
+        // SAFETY: The state bits are used only by this mutex.
+        let mut lock = unsafe { self.sender.lock(&self.state) };
+        let waker = lock.take();
+        drop(lock);
+        if let Some(waker) = waker {
+            crate::inner::wake(&waker);
+        }
+    }
+
+    /// Wakes every waiting receiver.
+    fn wake_all(&self) {
+        // SAFETY: The state bits are used only by this list.
+        for waker in unsafe { self.waiters.take_all(&self.state) } {
+            crate::inner::wake(&waker);
+        }
+    }
+}
+
+impl<T> Drop for Shared<T> {
+    fn drop(&mut self) {
+        self.waiters.drop(&self.state);
+        self.sender.drop(&self.state);
+
+        // Drop the value if present.
+        if self.state.load(Ordering::Acquire) & (1 << VALUE_PRESENT_BIT) != 0 {
+            // SAFETY: We just checked that the value is present.
+            unsafe { (*self.value.get()).assume_init_drop() };
+        }
+    }
+}
+
+unsafe impl<T: Send + Sync> Send for Shared<T> {}
+unsafe impl<T: Send + Sync> Sync for Shared<T> {}
+
+/// The sending half of a broadcast channel.
+pub struct Sender<T> {
+    shared: Arc<Shared<T>>,
+    did_send: bool,
+}
+
+impl<T> Sender<T> {
+    /// Closes the channel by causing an immediate drop
+    pub fn close(self) {}
+
+    /// true if every receiver has dropped.
+    ///
+    /// NOTE: The result may be instantly be out of date if it returns
+    /// false. A true result is final.
+    pub fn is_closed(&self) -> bool {
+        self.shared.receivers.load(Ordering::Acquire) == 0
+    }
+
+    /// Sends a message to every receiver. Fails if all the receivers
+    /// have dropped.
+    ///
+    /// This doesn't wait for the receivers to take it: use
+    /// [`Sender::taken`] for that.
+    pub fn send(&mut self, value: T) -> Result<(), Closed> {
+        if self.did_send {
+            return Err(Closed());
+        }
+        self.did_send = true;
+
+        // SAFETY: We only write once, before setting the present bit.
+        unsafe { (*self.shared.value.get()).write(value) };
+        self.shared
+            .state
+            .fetch_or(1 << VALUE_PRESENT_BIT, Ordering::Release);
+        self.shared.wake_all();
+
+        if self.is_closed() {
+            Err(Closed())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Waits for every receiver to take the value or drop.
+    ///
+    /// Receivers cloned after this has completed are not waited for.
+    pub fn taken(&mut self) -> Taken<'_, T> {
+        Taken { sender: self }
+    }
+
+    /// Removes our waker, so no receiver wakes a task which is no longer
+    /// waiting on it.
+    fn clear_waker(&self) {
+        // SAFETY: The state bits are used only by this mutex.
+        let mut lock = unsafe { self.shared.sender.lock(&self.shared.state) };
+        let old_waker = lock.take();
+        drop(lock);
+        drop(old_waker);
+    }
+}
+
+/// A future waiting for every receiver to take the value or drop.
+/// Created by [`Sender::taken`].
+///
+/// Dropping it removes its waker from the channel.
+#[derive(Debug)]
+pub struct Taken<'a, T> {
+    sender: &'a mut Sender<T>,
+}
+
+impl<T> Future for Taken<'_, T> {
+    type Output = ();
+    fn poll(self: Pin<&mut Self>, ctx: &mut Context) -> Poll<()> {
+        let shared = &Pin::into_inner(self).sender.shared;
+        if shared.pending.load(Ordering::Acquire) == 0 {
+            return Poll::Ready(());
+        }
+
+        // SAFETY: The state bits are used only by this mutex.
+        let mut lock = unsafe { shared.sender.lock(&shared.state) };
+        let old_waker = lock.replace(ctx.waker().clone());
+        drop(lock);
+        drop(old_waker);
+
+        // The last receiver may have finished before we registered.
+        if shared.pending.load(Ordering::Acquire) == 0 {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+impl<T> Drop for Taken<'_, T> {
+    fn drop(&mut self) {
+        self.sender.clear_waker();
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        if !self.did_send {
+            self.shared
+                .state
+                .fetch_or(1 << CLOSED_BIT, Ordering::Release);
+            self.shared.wake_all();
+        }
+    }
+}
+
+impl<T> fmt::Debug for Sender<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Sender")
+            .field("did_send", &self.did_send)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A receiving half of a broadcast channel.
+pub struct Receiver<T> {
+    shared: Arc<Shared<T>>,
+    // Which entry in the waiter list is ours.
+    id: usize,
+    did_receive: bool,
+}
+
+impl<T: Clone> Receiver<T> {
+    /// Closes this receiver by causing an immediate drop.
+    pub fn close(self) {}
+
+    /// true if the Sender has dropped without sending.
+    ///
+    /// NOTE: The result may be instantly be out of date if it returns
+    /// false. A true result is final.
+    pub fn is_closed(&self) -> bool {
+        self.shared.state.load(Ordering::Acquire) & (1 << CLOSED_BIT) != 0
+    }
+
+    /// true if the Sender has either sent or dropped.
+    pub(crate) fn is_finished(&self) -> bool {
+        self.shared.state.load(Ordering::Acquire) & (1 << VALUE_PRESENT_BIT | 1 << CLOSED_BIT) != 0
+    }
+
+    /// Attempts to receive, returning `None` if the Sender has not
+    /// sent a message yet.
+    ///
+    /// Once a result has been returned, further calls return
+    /// `Some(Err(Closed()))`.
+    pub fn try_recv(&mut self) -> Option<Result<T, Closed>> {
+        if self.did_receive {
+            return Some(Err(Closed()));
+        }
+        let result = self.shared.try_clone();
+        if result.is_some() {
+            self.did_receive = true;
+            self.shared.finish_receiver();
+        }
+        result
+    }
+}
+
+impl<T: Clone> Future for Receiver<T> {
+    type Output = Result<T, Closed>;
+    fn poll(self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Result<T, Closed>> {
+        let this = Pin::into_inner(self);
+        if let Some(result) = this.try_recv() {
+            return Poll::Ready(result);
+        }
+
+        // SAFETY: The state bits are used only by this list.
+        unsafe {
+            this.shared
+                .waiters
+                .register(&this.shared.state, this.id, ctx.waker())
+        };
+
+        // The value may have arrived before we registered.
+        match this.try_recv() {
+            Some(result) => {
+                // SAFETY: The state bits are used only by this list.
+                unsafe { this.shared.waiters.deregister(&this.shared.state, this.id) };
+                Poll::Ready(result)
+            }
+            None => Poll::Pending,
+        }
+    }
+}
+
+#[cfg(feature = "futures-core")]
+impl<T: Clone> futures_core::FusedFuture for Receiver<T> {
+    fn is_terminated(&self) -> bool {
+        self.did_receive
+    }
+}
+
+impl<T> Clone for Receiver<T> {
+    fn clone(&self) -> Self {
+        self.shared.receivers.fetch_add(1, Ordering::Relaxed);
+        self.shared.pending.fetch_add(1, Ordering::Relaxed);
+        Receiver {
+            shared: self.shared.clone(),
+            id: next_id(),
+            did_receive: false,
+        }
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        self.shared.receivers.fetch_sub(1, Ordering::AcqRel);
+        // SAFETY: The state bits are used only by this list.
+        unsafe { self.shared.waiters.deregister(&self.shared.state, self.id) };
+        if !self.did_receive {
+            self.shared.finish_receiver();
+        }
+    }
+}
+
+impl<T> fmt::Debug for Receiver<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Receiver")
+            .field("did_receive", &self.did_receive)
+            .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,101 @@
+//! A table of pending responses, keyed by request.
+
+use crate::{oneshot, Closed, Receiver, Sender};
+use alloc::collections::BTreeMap;
+use core::fmt;
+
+/// The size below which the map doesn't bother pruning.
+const MIN_PRUNE_LEN: usize = 16;
+
+/// Senders for pending responses, stored by key.
+///
+/// Each call to [`CompletionMap::insert`] hands out a Receiver for a
+/// key, and [`CompletionMap::complete`] later routes a value to it.
+///
+/// Keys whose Receivers have dropped are forgotten as the map is used:
+/// whenever it has doubled in size since it last looked, inserting or
+/// completing first prunes them.
+pub struct CompletionMap<K, T> {
+    senders: BTreeMap<K, Sender<T>>,
+    // The size at which to next prune.
+    prune_at: usize,
+}
+
+impl<K: Ord, T> CompletionMap<K, T> {
+    /// Creates an empty map.
+    pub fn new() -> Self {
+        CompletionMap {
+            senders: BTreeMap::new(),
+            prune_at: MIN_PRUNE_LEN,
+        }
+    }
+
+    /// Registers a key, returning the Receiver its value will be sent to.
+    ///
+    /// If the key was already registered, the previous Receiver is
+    /// closed.
+    pub fn insert(&mut self, key: K) -> Receiver<T> {
+        self.prune_if_grown();
+        let (sender, receiver) = oneshot();
+        self.senders.insert(key, sender);
+        receiver
+    }
+
+    /// Sends the value for a key and forgets the key. Fails if the key
+    /// is not registered or its Receiver has dropped.
+    pub fn complete(&mut self, key: &K, value: T) -> Result<(), Closed> {
+        self.prune_if_grown();
+        self.senders.remove(key).ok_or(Closed())?.send(value)
+    }
+
+    /// Forgets a key, closing its Receiver. Returns false if the key was
+    /// not registered.
+    pub fn cancel(&mut self, key: &K) -> bool {
+        self.senders.remove(key).is_some()
+    }
+
+    /// true if the key is registered.
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.senders.contains_key(key)
+    }
+
+    /// Forgets every key whose Receiver has dropped, without waiting for
+    /// the map to do so as it grows.
+    pub fn prune(&mut self) {
+        self.senders.retain(|_, sender| !sender.is_closed());
+        self.prune_at = (self.senders.len() * 2).max(MIN_PRUNE_LEN);
+    }
+
+    /// Prunes if the map has doubled in size since it was last pruned,
+    /// so the cost is spread across the calls that grew it.
+    fn prune_if_grown(&mut self) {
+        if self.senders.len() >= self.prune_at {
+            self.prune();
+        }
+    }
+
+    /// The number of registered keys, including any whose Receivers have
+    /// dropped since the map was last pruned.
+    pub fn len(&self) -> usize {
+        self.senders.len()
+    }
+
+    /// true if no keys are registered.
+    pub fn is_empty(&self) -> bool {
+        self.senders.is_empty()
+    }
+}
+
+impl<K: Ord, T> Default for CompletionMap<K, T> {
+    fn default() -> Self {
+        CompletionMap::new()
+    }
+}
+
+impl<K: fmt::Debug, T> fmt::Debug for CompletionMap<K, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CompletionMap")
+            .field("keys", &self.senders.keys())
+            .finish()
+    }
+}
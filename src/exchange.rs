@@ -0,0 +1,53 @@
+//! A rendezvous where each side hands the other a value.
+
+use crate::*;
+use core::future::Future;
+
+/// Create a new exchange pair. Each side offers a value and receives
+/// the one offered by the other.
+pub fn exchange<T, U>() -> (Exchanger<T, U>, Exchanger<U, T>) {
+    let (left_send, right_recv) = oneshot();
+    let (right_send, left_recv) = oneshot();
+    let left = Exchanger {
+        sender: left_send,
+        receiver: left_recv,
+    };
+    let right = Exchanger {
+        sender: right_send,
+        receiver: right_recv,
+    };
+    (left, right)
+}
+
+/// One side of an exchange, offering a `T` for a `U`. Created by
+/// [`exchange()`].
+#[derive(Debug)]
+pub struct Exchanger<T, U> {
+    sender: Sender<T>,
+    receiver: Receiver<U>,
+}
+
+impl<T, U> Exchanger<T, U> {
+    /// Offers a value and waits for the other side's. Fails if the
+    /// other side dropped without offering a value, or stopped waiting
+    /// for ours.
+    pub fn exchange(self, value: T) -> impl Future<Output = Result<U, Closed>> {
+        let Exchanger {
+            mut sender,
+            receiver,
+        } = self;
+        let sent = sender.send(value);
+        async move {
+            sent?;
+            receiver.await
+        }
+    }
+
+    /// Closes the exchange by causing an immediate drop.
+    pub fn close(self) {}
+
+    /// true if the other side has dropped.
+    pub fn is_closed(&self) -> bool {
+        self.sender.is_closed()
+    }
+}
@@ -0,0 +1,119 @@
+//! The layout of a channel's state word.
+//!
+//! Every channel keeps its state in a single `usize`. The bits below are
+//! part of the public API: they only change along with
+//! [`LAYOUT_VERSION`], so external tools inspecting a channel can rely
+//! on them.
+
+use core::fmt;
+
+/// Bumped whenever the meaning of any bit changes.
+pub const LAYOUT_VERSION: u32 = 1;
+
+/// The Sender's waker slot is locked.
+pub const SEND_LOCKED: usize = 1 << 0;
+/// The Sender has a waker registered.
+pub const SEND_PRESENT: usize = 1 << 1;
+/// The Receiver's waker slot is locked.
+pub const RECV_LOCKED: usize = 1 << 2;
+/// The Receiver has a waker registered.
+pub const RECV_PRESENT: usize = 1 << 3;
+/// A value has been sent and not yet taken.
+pub const VALUE_PRESENT: usize = 1 << 4;
+/// One side has closed the channel.
+pub const CLOSED: usize = 1 << 5;
+/// The Receiver has taken the value, observed the close or dropped.
+pub const RECV_DONE: usize = 1 << 6;
+/// The observers' waker list is locked. Only used with the `observer`
+/// feature.
+pub const OBSERVERS_LOCKED: usize = 1 << 7;
+/// At least one observer is waiting. Only used with the `observer`
+/// feature.
+pub const OBSERVERS_PRESENT: usize = 1 << 8;
+/// The Sender is waiting in `Sender::wait`.
+pub const SEND_WAITING: usize = 1 << 9;
+
+/// Every bit defined by this version of the layout.
+pub const ALL: usize = SEND_LOCKED
+    | SEND_PRESENT
+    | RECV_LOCKED
+    | RECV_PRESENT
+    | VALUE_PRESENT
+    | CLOSED
+    | RECV_DONE
+    | OBSERVERS_LOCKED
+    | OBSERVERS_PRESENT
+    | SEND_WAITING;
+
+/// A snapshot of a channel's state word.
+#[derive(Clone, Copy, Eq, PartialEq)]
+pub struct Flags(usize);
+
+impl Flags {
+    /// Parses a raw state word, failing if any undefined bit is set.
+    pub fn from_bits(bits: usize) -> Option<Flags> {
+        if bits & !ALL == 0 {
+            Some(Flags(bits))
+        } else {
+            None
+        }
+    }
+
+    /// Parses a raw state word, ignoring any undefined bits.
+    pub fn from_bits_truncate(bits: usize) -> Flags {
+        Flags(bits & ALL)
+    }
+
+    /// The raw state word.
+    pub fn to_bits(self) -> usize {
+        self.0
+    }
+
+    /// true if every bit in `mask` is set.
+    pub fn contains(self, mask: usize) -> bool {
+        self.0 & mask == mask
+    }
+
+    /// A short human-readable summary of the channel's state.
+    pub(crate) fn describe(self) -> &'static str {
+        if self.contains(CLOSED) {
+            "closed"
+        } else if self.contains(VALUE_PRESENT) {
+            "full"
+        } else if self.contains(RECV_DONE) {
+            "done"
+        } else {
+            "open"
+        }
+    }
+}
+
+const NAMES: [(usize, &str); 10] = [
+    (SEND_LOCKED, "SEND_LOCKED"),
+    (SEND_PRESENT, "SEND_PRESENT"),
+    (RECV_LOCKED, "RECV_LOCKED"),
+    (RECV_PRESENT, "RECV_PRESENT"),
+    (VALUE_PRESENT, "VALUE_PRESENT"),
+    (CLOSED, "CLOSED"),
+    (RECV_DONE, "RECV_DONE"),
+    (OBSERVERS_LOCKED, "OBSERVERS_LOCKED"),
+    (OBSERVERS_PRESENT, "OBSERVERS_PRESENT"),
+    (SEND_WAITING, "SEND_WAITING"),
+];
+
+impl fmt::Debug for Flags {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Flags(")?;
+        let mut first = true;
+        for (bit, name) in NAMES {
+            if self.contains(bit) {
+                if !first {
+                    f.write_str(" | ")?;
+                }
+                f.write_str(name)?;
+                first = false;
+            }
+        }
+        f.write_str(")")
+    }
+}
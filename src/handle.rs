@@ -0,0 +1,87 @@
+//! Delivering the output of a future through a oneshot channel.
+//!
+//! This is the building block executors use to implement join handles.
+
+use crate::*;
+use alloc::boxed::Box;
+use core::task::{Context, Poll};
+use core::{future::Future, pin::Pin};
+
+/// Wraps a future so that its output is delivered to the returned
+/// [`Handle`].
+///
+/// The [`Task`] should be spawned onto an executor. It completes early
+/// if the handle is aborted or dropped.
+pub fn with_handle<F: Future>(future: F) -> (Task<F>, Handle<F::Output>) {
+    let (sender, receiver) = oneshot();
+    let task = Task {
+        future: Box::pin(future),
+        sender: Some(sender),
+    };
+    (task, Handle { receiver })
+}
+
+/// A future which runs the wrapped future and sends its output to the
+/// [`Handle`]. Created by [`with_handle`].
+pub struct Task<F: Future> {
+    future: Pin<Box<F>>,
+    sender: Option<Sender<F::Output>>,
+}
+
+impl<F: Future> Future for Task<F> {
+    type Output = ();
+    fn poll(self: Pin<&mut Self>, ctx: &mut Context) -> Poll<()> {
+        let this = Pin::into_inner(self);
+        let Some(sender) = this.sender.as_mut() else {
+            return Poll::Ready(());
+        };
+
+        // Stop as soon as nobody wants the output.
+        if sender.poll_closed(ctx).is_ready() {
+            this.sender = None;
+            return Poll::Ready(());
+        }
+
+        match this.future.as_mut().poll(ctx) {
+            Poll::Ready(value) => {
+                let _ = sender.send(value);
+                this.sender = None;
+                Poll::Ready(())
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl<F: Future> core::fmt::Debug for Task<F> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Task")
+            .field("finished", &self.sender.is_none())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Waits for the output of a [`Task`]. Created by [`with_handle`].
+///
+/// Resolves to [`Closed`] if the task was dropped before finishing.
+#[derive(Debug)]
+pub struct Handle<T> {
+    receiver: Receiver<T>,
+}
+
+impl<T> Handle<T> {
+    /// Stops the task the next time it is polled, discarding its output.
+    pub fn abort(self) {}
+
+    /// true if the task was dropped before finishing.
+    pub fn is_closed(&self) -> bool {
+        self.receiver.is_closed()
+    }
+}
+
+impl<T> Future for Handle<T> {
+    type Output = Result<T, Closed>;
+    fn poll(self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Result<T, Closed>> {
+        Pin::new(&mut Pin::into_inner(self).receiver).poll(ctx)
+    }
+}
@@ -1,15 +1,29 @@
+use crate::flags::{self, Flags};
 use crate::mutex::{Mutex, MutexGuard};
+#[cfg(feature = "observer")]
+use crate::waiters::Waiters;
+use alloc::sync::Arc;
+#[cfg(feature = "observer")]
+use alloc::sync::Weak;
 use core::cell::UnsafeCell;
-use core::mem::MaybeUninit;
+use core::mem::{ManuallyDrop, MaybeUninit};
+use core::ops::Deref;
+use core::ptr::NonNull;
 use core::sync::atomic::{AtomicUsize, Ordering};
 use core::task::Waker;
 
-const SEND_LOCKED_BIT: usize = 0;
-const SEND_PRESENT_BIT: usize = 1;
-const RECV_LOCKED_BIT: usize = 2;
-const RECV_PRESENT_BIT: usize = 3;
-const VALUE_PRESENT_BIT: usize = 4;
-const CLOSED_BIT: usize = 5;
+const SEND_LOCKED_BIT: usize = flags::SEND_LOCKED.trailing_zeros() as usize;
+const SEND_PRESENT_BIT: usize = flags::SEND_PRESENT.trailing_zeros() as usize;
+const RECV_LOCKED_BIT: usize = flags::RECV_LOCKED.trailing_zeros() as usize;
+const RECV_PRESENT_BIT: usize = flags::RECV_PRESENT.trailing_zeros() as usize;
+const VALUE_PRESENT_BIT: usize = flags::VALUE_PRESENT.trailing_zeros() as usize;
+const CLOSED_BIT: usize = flags::CLOSED.trailing_zeros() as usize;
+const RECV_DONE_BIT: usize = flags::RECV_DONE.trailing_zeros() as usize;
+#[cfg(feature = "observer")]
+const OBSERVERS_LOCKED_BIT: usize = flags::OBSERVERS_LOCKED.trailing_zeros() as usize;
+#[cfg(feature = "observer")]
+const OBSERVERS_PRESENT_BIT: usize = flags::OBSERVERS_PRESENT.trailing_zeros() as usize;
+const SEND_WAITING_BIT: usize = flags::SEND_WAITING.trailing_zeros() as usize;
 
 /// State of the value after taking it.
 pub(crate) enum InnerValue<T> {
@@ -24,11 +38,31 @@ pub(crate) struct Inner<T> {
     state: AtomicUsize,
 
     // Waker for sender and receiver.
-    send: Mutex<Waker, SEND_LOCKED_BIT, SEND_PRESENT_BIT>,
-    recv: Mutex<Waker, RECV_LOCKED_BIT, RECV_PRESENT_BIT>,
+    send: Mutex<Waker, SEND_PRESENT_BIT, SEND_LOCKED_BIT>,
+    recv: Mutex<Waker, RECV_PRESENT_BIT, RECV_LOCKED_BIT>,
+
+    // Wakers of observers waiting for the channel to finish, by id.
+    #[cfg(feature = "observer")]
+    observers: Waiters<OBSERVERS_PRESENT_BIT, OBSERVERS_LOCKED_BIT>,
 
     // Value of the channel (present if VALUE_PRESENT_BIT is set)
     value: UnsafeCell<MaybeUninit<T>>,
+
+    // Priority hints each side attaches to its waker.
+    #[cfg(feature = "priority")]
+    pub(crate) send_priority: core::sync::atomic::AtomicU8,
+    #[cfg(feature = "priority")]
+    pub(crate) recv_priority: core::sync::atomic::AtomicU8,
+
+    // Number of times each side has been woken.
+    #[cfg(feature = "diagnostics")]
+    send_wakes: AtomicUsize,
+    #[cfg(feature = "diagnostics")]
+    recv_wakes: AtomicUsize,
+
+    // Name given to the channel in logs, if any.
+    #[cfg(feature = "tracing")]
+    label: Option<&'static str>,
 }
 
 impl<T> Inner<T> {
@@ -38,27 +72,70 @@ impl<T> Inner<T> {
             state: AtomicUsize::new(0),
             send: Mutex::new(),
             recv: Mutex::new(),
+            #[cfg(feature = "observer")]
+            observers: Waiters::new(),
             value: UnsafeCell::new(MaybeUninit::uninit()),
+            #[cfg(feature = "priority")]
+            send_priority: core::sync::atomic::AtomicU8::new(0),
+            #[cfg(feature = "priority")]
+            recv_priority: core::sync::atomic::AtomicU8::new(0),
+            #[cfg(feature = "diagnostics")]
+            send_wakes: AtomicUsize::new(0),
+            #[cfg(feature = "diagnostics")]
+            recv_wakes: AtomicUsize::new(0),
+            #[cfg(feature = "tracing")]
+            label: None,
         }
     }
 
-    /// Attempts to take the value from the channel.
+    /// Attempts to take the value from the channel on behalf of the
+    /// receiver. Once it returns the value or reports the channel closed,
+    /// the receiver is marked as finished, and the caller must follow up
+    /// with [`Inner::wake_finished`] once it holds no locks.
     pub fn try_take(&self) -> InnerValue<T> {
-        // Load the state and clear the present bit
-        let state_snapshot = self
+        // A plain load keeps polling an empty channel free of contended writes.
+        let state = self.state.load(Ordering::Acquire);
+
+        if state & (1 << VALUE_PRESENT_BIT) != 0 {
+            // Only the receiver clears the value or marks itself finished,
+            // so both bits flip together in a single write.
+            self.state.fetch_xor(
+                1 << VALUE_PRESENT_BIT | 1 << RECV_DONE_BIT,
+                Ordering::AcqRel,
+            );
+            // SAFETY: We just checked that the value is present and cleared the present bit.
+            InnerValue::Present(unsafe { (*self.value.get()).assume_init_read() })
+        } else if state & (1 << CLOSED_BIT) != 0 {
+            // Closed bit is set
+            self.state.fetch_or(1 << RECV_DONE_BIT, Ordering::AcqRel);
+            InnerValue::Closed
+        } else {
+            InnerValue::Pending
+        }
+    }
+
+    /// Takes the value back out of the channel after the receiver has
+    /// dropped without taking it.
+    pub fn take_back(&self) -> Option<T> {
+        let state = self
             .state
             .fetch_and(!(1 << VALUE_PRESENT_BIT), Ordering::Acquire);
+        if state & (1 << VALUE_PRESENT_BIT) == 0 {
+            return None;
+        }
+        // SAFETY: We just checked that the value is present and cleared the present bit.
+        Some(unsafe { (*self.value.get()).assume_init_read() })
+    }
 
-        if state_snapshot & (1 << VALUE_PRESENT_BIT) == 0 {
-            if self.state.load(Ordering::Acquire) & (1 << CLOSED_BIT) != 0 {
-                // Closed bit is set
-                InnerValue::Closed
-            } else {
-                InnerValue::Pending
-            }
+    /// Borrows the value if present.
+    ///
+    /// SAFETY: Must only be called by the receiver, since nothing else
+    /// may take the value while the borrow lives.
+    pub unsafe fn peek(&self) -> Option<&T> {
+        if self.state.load(Ordering::Acquire) & (1 << VALUE_PRESENT_BIT) != 0 {
+            Some((*self.value.get()).assume_init_ref())
         } else {
-            // SAFETY: We just checked that the value is present and cleared the present bit.
-            InnerValue::Present(unsafe { (*self.value.get()).assume_init_read() })
+            None
         }
     }
 
@@ -74,24 +151,175 @@ impl<T> Inner<T> {
             .fetch_or(1 << VALUE_PRESENT_BIT, Ordering::Release);
     }
 
-    pub fn lock_send(&self) -> MutexGuard<'_, Waker, SEND_LOCKED_BIT, SEND_PRESENT_BIT> {
+    pub fn lock_send(&self) -> MutexGuard<'_, Waker, SEND_PRESENT_BIT, SEND_LOCKED_BIT> {
         // SAFETY: The state bits are used only by this mutex.
         unsafe { self.send.lock(&self.state) }
     }
 
-    pub fn lock_recv(&self) -> MutexGuard<'_, Waker, RECV_LOCKED_BIT, RECV_PRESENT_BIT> {
+    pub fn lock_recv(&self) -> MutexGuard<'_, Waker, RECV_PRESENT_BIT, RECV_LOCKED_BIT> {
         // SAFETY: The state bits are used only by this mutex.
         unsafe { self.recv.lock(&self.state) }
     }
 
     /// Marks the channel as closed and returns true if it was not closed before.
     pub fn mark_closed(&self) -> bool {
-        self.state.fetch_or(1 << CLOSED_BIT, Ordering::Acquire) & (1 << CLOSED_BIT) == 0
+        let state = self.state.fetch_or(1 << CLOSED_BIT, Ordering::AcqRel);
+        if state & (1 << CLOSED_BIT) != 0 {
+            return false;
+        }
+        #[cfg(feature = "observer")]
+        self.wake_observers();
+        true
     }
 
     pub fn is_closed(&self) -> bool {
         self.state.load(Ordering::Acquire) & (1 << CLOSED_BIT) != 0
     }
+
+    /// Marks the receiver as finished with the channel, waking the sender
+    /// if it has a waker registered.
+    pub fn finish_recv(&self) {
+        let state = self.state.fetch_or(1 << RECV_DONE_BIT, Ordering::AcqRel);
+        if state & (1 << RECV_DONE_BIT) == 0 {
+            self.wake_finished();
+        }
+    }
+
+    /// Wakes the sender, if it has a waker registered, and any observers
+    /// after the receiver has been marked as finished.
+    pub fn wake_finished(&self) {
+        // Registering a waker and marking the receiver finished both write
+        // the state word, so either we see the waker or the waiter sees
+        // that we are finished.
+        if self.state.load(Ordering::Acquire) & (1 << SEND_PRESENT_BIT) != 0 {
+            let mut send_lock = self.lock_send();
+            let waker = send_lock.take();
+            drop(send_lock);
+            if let Some(waker) = waker {
+                self.wake_send(&waker);
+            }
+        }
+        #[cfg(feature = "observer")]
+        self.wake_observers();
+    }
+
+    /// Wakes the sender if it is in `Sender::wait`, to tell it that the
+    /// receiver is now waiting. Senders only waiting for the receiver to
+    /// go away are left alone.
+    pub fn wake_waiting_sender(&self) {
+        // `Sender::wait` sets the bit while holding the receive lock, so a
+        // receiver which registered after it will see the bit here.
+        if self.state.load(Ordering::Acquire) & (1 << SEND_WAITING_BIT) == 0 {
+            return;
+        }
+        let send_lock = self.lock_send();
+        let waker = send_lock.get().cloned();
+        drop(send_lock);
+        if let Some(waker) = waker {
+            self.wake_send(&waker);
+        }
+    }
+
+    /// true once the channel is closed or the receiver is finished with it.
+    #[cfg(feature = "observer")]
+    pub fn is_finished(&self) -> bool {
+        self.state.load(Ordering::Acquire) & (1 << CLOSED_BIT | 1 << RECV_DONE_BIT) != 0
+    }
+
+    /// Adds or updates the waker registered by the observer `id`.
+    #[cfg(feature = "observer")]
+    pub fn register_observer(&self, id: usize, waker: &Waker) {
+        // SAFETY: The state bits are used only by this list.
+        unsafe { self.observers.register(&self.state, id, waker) };
+    }
+
+    /// Removes the waker registered by the observer `id`, if any.
+    #[cfg(feature = "observer")]
+    pub fn deregister_observer(&self, id: usize) {
+        // SAFETY: The state bits are used only by this list.
+        unsafe { self.observers.deregister(&self.state, id) };
+    }
+
+    /// Wakes every observer waiting for the channel to finish.
+    #[cfg(feature = "observer")]
+    fn wake_observers(&self) {
+        // SAFETY: The state bits are used only by this list.
+        for waker in unsafe { self.observers.take_all(&self.state) } {
+            wake(&waker);
+        }
+    }
+
+    /// Records whether the sender is waiting in `Sender::wait`.
+    pub fn set_send_waiting(&self, waiting: bool) {
+        if waiting {
+            self.state
+                .fetch_or(1 << SEND_WAITING_BIT, Ordering::Release);
+        } else {
+            self.state
+                .fetch_and(!(1 << SEND_WAITING_BIT), Ordering::Release);
+        }
+    }
+
+    /// true once the receiver has taken the value, observed the channel
+    /// closing or dropped.
+    pub fn is_recv_done(&self) -> bool {
+        self.state.load(Ordering::Acquire) & (1 << RECV_DONE_BIT) != 0
+    }
+
+    /// A snapshot of the state word.
+    pub fn flags(&self) -> Flags {
+        Flags::from_bits_truncate(self.state.load(Ordering::Acquire))
+    }
+
+    /// Wakes the sender with a waker taken from (or still in) its slot.
+    pub fn wake_send(&self, waker: &Waker) {
+        #[cfg(feature = "diagnostics")]
+        self.send_wakes.fetch_add(1, Ordering::Relaxed);
+        #[cfg(feature = "tracing")]
+        tracing::trace!(
+            channel = self.id(),
+            label = self.label,
+            side = "sender",
+            "waking"
+        );
+        wake(waker);
+    }
+
+    /// Wakes the receiver with a waker taken from its slot.
+    pub fn wake_recv(&self, waker: &Waker) {
+        #[cfg(feature = "diagnostics")]
+        self.recv_wakes.fetch_add(1, Ordering::Relaxed);
+        #[cfg(feature = "tracing")]
+        tracing::trace!(
+            channel = self.id(),
+            label = self.label,
+            side = "receiver",
+            "waking"
+        );
+        wake(waker);
+    }
+
+    /// Identifies the channel in logs, by the address of its state.
+    #[cfg(feature = "tracing")]
+    pub fn id(&self) -> usize {
+        self as *const Self as usize
+    }
+
+    /// The name the channel was created with, if any.
+    #[cfg(feature = "tracing")]
+    pub fn label(&self) -> Option<&'static str> {
+        self.label
+    }
+
+    #[cfg(feature = "diagnostics")]
+    pub fn send_wakes(&self) -> usize {
+        self.send_wakes.load(Ordering::Relaxed)
+    }
+
+    #[cfg(feature = "diagnostics")]
+    pub fn recv_wakes(&self) -> usize {
+        self.recv_wakes.load(Ordering::Relaxed)
+    }
 }
 
 impl<T> Drop for Inner<T> {
@@ -99,6 +327,8 @@ impl<T> Drop for Inner<T> {
         // Make sure to release drop the mutexes.
         self.send.drop(&self.state);
         self.recv.drop(&self.state);
+        #[cfg(feature = "observer")]
+        self.observers.drop(&self.state);
 
         // Drop the value if present.
         if self.state.load(Ordering::Acquire) & (1 << VALUE_PRESENT_BIT) != 0 {
@@ -108,5 +338,99 @@ impl<T> Drop for Inner<T> {
     }
 }
 
+/// A counted reference to a channel's shared state.
+///
+/// This is an `Arc<Inner<T>>` whose layout is defined: a single non-null
+/// pointer to the `Inner` itself, as returned by `Arc::into_raw`.
+#[repr(transparent)]
+pub(crate) struct Shared<T>(NonNull<Inner<T>>);
+
+const _: () = assert!(core::mem::size_of::<Shared<()>>() == core::mem::size_of::<*const ()>());
+const _: () = assert!(core::mem::align_of::<Shared<()>>() == core::mem::align_of::<*const ()>());
+
+impl<T> Shared<T> {
+    pub(crate) fn new() -> Self {
+        Self::from_inner(Inner::new())
+    }
+
+    /// Like [`Shared::new`], naming the channel in logs.
+    #[cfg(feature = "tracing")]
+    pub(crate) fn labelled(label: &'static str) -> Self {
+        let mut inner = Inner::new();
+        inner.label = Some(label);
+        Self::from_inner(inner)
+    }
+
+    fn from_inner(inner: Inner<T>) -> Self {
+        let raw = Arc::into_raw(Arc::new(inner));
+        // SAFETY: Arc::into_raw never returns null.
+        Shared(unsafe { NonNull::new_unchecked(raw.cast_mut()) })
+    }
+
+    /// Gives up this reference without decrementing the count.
+    pub(crate) fn into_raw(self) -> *const Inner<T> {
+        let this = ManuallyDrop::new(self);
+        this.0.as_ptr()
+    }
+
+    /// Reclaims a reference given up with [`Shared::into_raw`].
+    ///
+    /// SAFETY: `raw` must have come from `Shared::<T>::into_raw`, and must
+    /// be reclaimed at most once.
+    pub(crate) unsafe fn from_raw(raw: *const Inner<T>) -> Self {
+        Shared(NonNull::new_unchecked(raw.cast_mut()))
+    }
+
+    /// Borrows the reference as the `Arc` it was created from.
+    fn as_arc(&self) -> ManuallyDrop<Arc<Inner<T>>> {
+        // SAFETY: The pointer came from Arc::into_raw and we hold a count.
+        ManuallyDrop::new(unsafe { Arc::from_raw(self.0.as_ptr()) })
+    }
+
+    pub(crate) fn strong_count(&self) -> usize {
+        Arc::strong_count(&self.as_arc())
+    }
+
+    #[cfg(feature = "observer")]
+    pub(crate) fn downgrade(&self) -> Weak<Inner<T>> {
+        Arc::downgrade(&self.as_arc())
+    }
+}
+
+impl<T> Clone for Shared<T> {
+    fn clone(&self) -> Self {
+        // SAFETY: The pointer came from Arc::into_raw and we hold a count.
+        unsafe { Arc::increment_strong_count(self.0.as_ptr()) };
+        Shared(self.0)
+    }
+}
+
+impl<T> Drop for Shared<T> {
+    fn drop(&mut self) {
+        // SAFETY: The pointer came from Arc::into_raw and we give up our count.
+        drop(unsafe { Arc::from_raw(self.0.as_ptr()) });
+    }
+}
+
+impl<T> Deref for Shared<T> {
+    type Target = Inner<T>;
+    fn deref(&self) -> &Inner<T> {
+        // SAFETY: We hold a count, so the Inner is alive.
+        unsafe { self.0.as_ref() }
+    }
+}
+
+unsafe impl<T: Send> Send for Shared<T> {}
+unsafe impl<T: Send> Sync for Shared<T> {}
+
+/// Wakes the other side of a channel.
+#[inline(always)]
+pub(crate) fn wake(waker: &Waker) {
+    #[cfg(feature = "sim")]
+    crate::sim::wake(waker);
+    #[cfg(not(feature = "sim"))]
+    waker.wake_by_ref();
+}
+
 unsafe impl<T: Send> Send for Inner<T> {}
 unsafe impl<T: Send> Sync for Inner<T> {}
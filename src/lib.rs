@@ -3,33 +3,160 @@
 //! Unique feature: wait for receiver to be waiting.
 //!
 //! Also supports the full range of things you'd expect.
+//!
+//! ## Wake ordering
+//!
+//! A side is only ever woken after the change it is being told about is
+//! visible: when the Receiver's waker fires, the value has already been
+//! stored or the channel closed, so polling again will complete. When
+//! the Sender's waker fires, the Receiver is already waiting, finished
+//! or gone. Wakers are always called outside the channel's locks.
+//!
+//! Wakes are not coalesced: every event wakes the registered waker once.
 #![no_std]
 extern crate alloc;
-use alloc::sync::Arc;
+#[cfg(feature = "std")]
+extern crate std;
+use core::fmt;
 
 mod inner;
-pub(crate) use inner::Inner;
+pub(crate) use inner::{Inner, Shared};
 
 mod sender;
-pub use sender::Sender;
+pub use sender::{pipe, send_to_all, Completer, Sender, Wait, WaitClosed};
 
 mod receiver;
 mod mutex;
+mod waiters;
 
 pub use receiver::Receiver;
 
+#[cfg(feature = "parking")]
+mod parking;
+#[cfg(feature = "parking")]
+pub use parking::{block_on, block_on_spinning};
+
+pub mod broadcast;
+pub use broadcast::broadcast;
+
+pub mod completion;
+pub use completion::CompletionMap;
+
+pub mod exchange;
+pub use exchange::exchange;
+
+pub mod flags;
+
+pub mod notify;
+pub use notify::Notify;
+
+#[cfg(feature = "observer")]
+pub mod observer;
+#[cfg(feature = "observer")]
+pub use observer::Observer;
+
+pub mod select;
+pub use select::Selector;
+
+pub mod shutdown;
+pub use shutdown::shutdown;
+
+#[cfg(feature = "handle")]
+pub mod handle;
+
+#[cfg(feature = "model")]
+pub mod model;
+
+#[cfg(feature = "sim")]
+pub mod sim;
+
+pub mod timer;
+
+#[cfg(feature = "test-util")]
+pub mod test_util;
+
+mod map;
+pub use map::{FlattenReceiver, InspectReceiver, InspectSender, MappedReceiver, MappedSender};
+
 /// Create a new oneshot channel pair.
 pub fn oneshot<T>() -> (Sender<T>, Receiver<T>) {
-    let inner = Arc::new(Inner::new());
+    let inner = Shared::new();
     let sender = Sender::new(inner.clone());
     let receiver = Receiver::new(inner);
     (sender, receiver)
 }
 
+/// Create a new oneshot channel pair, named `label` in the spans and
+/// events it logs.
+#[cfg(feature = "tracing")]
+pub fn oneshot_labelled<T>(label: &'static str) -> (Sender<T>, Receiver<T>) {
+    let inner = Shared::labelled(label);
+    let sender = Sender::new(inner.clone());
+    let receiver = Receiver::new(inner);
+    (sender, receiver)
+}
+
+/// Tears down a task's handles: closes `sender` (unless it has sent),
+/// then takes any value already waiting on `receiver` before closing it.
+///
+/// The handles may belong to the same channel or to different ones.
+/// Closing the sender first means its peer learns of the teardown
+/// before the receiver's peer does, and no value that has already
+/// arrived is dropped unseen.
+pub fn close_pair<T, U>(sender: Sender<T>, receiver: Receiver<U>) -> Option<U> {
+    drop(sender);
+    receiver.try_recv().ok()
+}
+
+/// The total number of times any channel has spun waiting for one of
+/// its internal locks, for measuring contention.
+#[cfg(feature = "diagnostics")]
+pub fn lock_spins() -> usize {
+    mutex::SPINS.load(core::sync::atomic::Ordering::Relaxed)
+}
+
 /// An empty struct that signifies the channel is closed.
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub struct Closed();
 
+/// We couldn't send a message. Carries the message that wasn't sent.
+#[derive(Clone, Copy, Eq, PartialEq)]
+pub struct SendError<T>(pub T);
+
+impl<T> SendError<T> {
+    /// Returns the message that wasn't sent.
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+
+    /// Passes the message that wasn't sent to `f`, for example to route
+    /// it elsewhere, leaving only the error.
+    pub fn salvage<F: FnOnce(T)>(self, f: F) -> Closed {
+        f(self.0);
+        Closed()
+    }
+}
+
+impl<T> fmt::Debug for SendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SendError").finish_non_exhaustive()
+    }
+}
+
+impl<T> fmt::Display for SendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("channel closed")
+    }
+}
+
+impl<T> core::error::Error for SendError<T> {}
+
+impl<T> From<SendError<T>> for Closed {
+    fn from(_: SendError<T>) -> Self {
+        Closed()
+    }
+}
+
 /// We couldn't receive a message.
 #[derive(Debug)]
 pub enum TryRecvError<T> {
@@ -39,3 +166,111 @@ pub enum TryRecvError<T> {
     Closed,
 }
 
+
+impl fmt::Display for Closed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("channel closed")
+    }
+}
+
+impl core::error::Error for Closed {}
+
+impl Closed {
+    /// Attaches a tag identifying which channel closed.
+    pub fn with_tag<K>(self, tag: K) -> TaggedClosed<K> {
+        TaggedClosed(tag)
+    }
+}
+
+/// A [`Closed`] error carrying a tag identifying the channel.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct TaggedClosed<K>(pub K);
+
+impl<K: fmt::Display> fmt::Display for TaggedClosed<K> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "channel {} closed", self.0)
+    }
+}
+
+impl<K: fmt::Debug + fmt::Display> core::error::Error for TaggedClosed<K> {}
+
+impl<K> From<TaggedClosed<K>> for Closed {
+    fn from(_: TaggedClosed<K>) -> Self {
+        Closed()
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<Closed> for std::io::Error {
+    fn from(closed: Closed) -> Self {
+        std::io::Error::new(std::io::ErrorKind::BrokenPipe, closed)
+    }
+}
+
+impl<T> fmt::Display for TryRecvError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TryRecvError::Empty(_) => f.write_str("channel empty"),
+            TryRecvError::Closed => f.write_str("channel closed"),
+        }
+    }
+}
+
+impl<T: fmt::Debug> core::error::Error for TryRecvError<T> {}
+
+/// Any of the errors the channels report, for exposing channel-backed
+/// APIs through a single error type. New variants may be added.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum Error {
+    /// The channel closed. See [`Closed`].
+    Closed,
+    /// A deadline passed. See [`TimedOut`](timer::TimedOut).
+    TimedOut,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Closed => fmt::Display::fmt(&Closed(), f),
+            Error::TimedOut => fmt::Display::fmt(&timer::TimedOut(), f),
+        }
+    }
+}
+
+impl core::error::Error for Error {}
+
+impl From<Closed> for Error {
+    fn from(_: Closed) -> Self {
+        Error::Closed
+    }
+}
+
+impl<T> From<SendError<T>> for Error {
+    fn from(_: SendError<T>) -> Self {
+        Error::Closed
+    }
+}
+
+impl<K> From<TaggedClosed<K>> for Error {
+    fn from(_: TaggedClosed<K>) -> Self {
+        Error::Closed
+    }
+}
+
+impl From<timer::TimedOut> for Error {
+    fn from(_: timer::TimedOut) -> Self {
+        Error::TimedOut
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<Error> for std::io::Error {
+    fn from(error: Error) -> Self {
+        let kind = match error {
+            Error::Closed => std::io::ErrorKind::BrokenPipe,
+            Error::TimedOut => std::io::ErrorKind::TimedOut,
+        };
+        std::io::Error::new(kind, error)
+    }
+}
@@ -0,0 +1,219 @@
+use crate::*;
+use core::fmt;
+use core::marker::PhantomData;
+use core::task::{Context, Poll};
+use core::{future::Future, pin::Pin};
+
+/// Implements what every receiver adapter shares on top of the
+/// adapter's own `apply`, which turns the inner receiver's result into
+/// the adapter's output.
+macro_rules! receiver_adapter {
+    ($name:ident<$($gen:ident),*>, $item:ty => $output:ty, [$($bound:tt)*]) => {
+        impl<$($gen),*> $name<$($gen),*> where $($bound)* {
+            /// Closes the channel by causing an immediate drop.
+            pub fn close(self) {}
+
+            /// true if the Sender has dropped without sending.
+            ///
+            /// See [`Receiver::is_closed`].
+            pub fn is_closed(&self) -> bool {
+                self.receiver.is_closed()
+            }
+
+            /// Attempts to receive, returning `None` if the Sender has not
+            /// sent us a message yet.
+            ///
+            /// Once a result has been returned, further calls report the
+            /// channel closed.
+            pub fn try_recv(&mut self) -> Option<$output> {
+                let result = self.receiver.take()?;
+                Some(self.apply(result))
+            }
+
+            /// Returns the underlying receiver, discarding any function.
+            pub fn into_inner(self) -> Receiver<$item> {
+                self.receiver
+            }
+        }
+
+        impl<$($gen),*> Future for $name<$($gen),*> where $($bound)*, Self: Unpin {
+            type Output = $output;
+            fn poll(self: Pin<&mut Self>, ctx: &mut Context) -> Poll<$output> {
+                let this = Pin::into_inner(self);
+                Pin::new(&mut this.receiver).poll(ctx).map(|r| this.apply(r))
+            }
+        }
+
+        #[cfg(feature = "futures-core")]
+        impl<$($gen),*> futures_core::FusedFuture for $name<$($gen),*>
+        where
+            $($bound)*,
+            Self: Unpin,
+        {
+            fn is_terminated(&self) -> bool {
+                self.receiver.is_terminated()
+            }
+        }
+
+        impl<$($gen),*> fmt::Debug for $name<$($gen),*> {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.debug_struct(stringify!($name))
+                    .field("receiver", &self.receiver)
+                    .finish_non_exhaustive()
+            }
+        }
+    };
+}
+
+/// Implements what every sender adapter shares. Sending is left to the
+/// adapter.
+macro_rules! sender_adapter {
+    ($name:ident<$($gen:ident),*>, $item:ty, [$($bound:tt)*]) => {
+        impl<$($gen),*> $name<$($gen),*> where $($bound)* {
+            /// Closes the channel by causing an immediate drop.
+            pub fn close(self) {}
+
+            /// true if the channel is closed.
+            ///
+            /// See [`Sender::is_closed`].
+            pub fn is_closed(&self) -> bool {
+                self.sender.is_closed()
+            }
+
+            /// Returns the underlying sender, discarding the function.
+            pub fn into_inner(self) -> Sender<$item> {
+                self.sender
+            }
+        }
+
+        impl<$($gen),*> fmt::Debug for $name<$($gen),*> {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.debug_struct(stringify!($name))
+                    .field("sender", &self.sender)
+                    .finish_non_exhaustive()
+            }
+        }
+    };
+}
+
+/// A [`Receiver`] that applies a function to the value as it is
+/// received. Created by [`Receiver::map`].
+pub struct MappedReceiver<T, U, F> {
+    receiver: Receiver<T>,
+    f: F,
+    _output: PhantomData<fn() -> U>,
+}
+
+impl<T, U, F: FnMut(T) -> U> MappedReceiver<T, U, F> {
+    pub(crate) fn new(receiver: Receiver<T>, f: F) -> Self {
+        MappedReceiver {
+            receiver,
+            f,
+            _output: PhantomData,
+        }
+    }
+
+    fn apply(&mut self, result: Result<T, Closed>) -> Result<U, Closed> {
+        result.map(&mut self.f)
+    }
+}
+
+receiver_adapter!(MappedReceiver<T, U, F>, T => Result<U, Closed>, [F: FnMut(T) -> U]);
+
+/// A [`Receiver`] of a `Result` which folds the channel closing into
+/// the payload's error type. Created by [`Receiver::flatten`].
+pub struct FlattenReceiver<T, E> {
+    receiver: Receiver<Result<T, E>>,
+}
+
+impl<T, E: From<Closed>> FlattenReceiver<T, E> {
+    pub(crate) fn new(receiver: Receiver<Result<T, E>>) -> Self {
+        FlattenReceiver { receiver }
+    }
+
+    fn apply(&mut self, result: Result<Result<T, E>, Closed>) -> Result<T, E> {
+        result.unwrap_or_else(|c| Err(c.into()))
+    }
+}
+
+receiver_adapter!(FlattenReceiver<T, E>, Result<T, E> => Result<T, E>, [E: From<Closed>]);
+
+/// A [`Receiver`] that calls a function with a reference to the value
+/// as it is received. Created by [`Receiver::inspect`].
+pub struct InspectReceiver<T, F> {
+    receiver: Receiver<T>,
+    f: F,
+}
+
+impl<T, F: FnMut(&T)> InspectReceiver<T, F> {
+    pub(crate) fn new(receiver: Receiver<T>, f: F) -> Self {
+        InspectReceiver { receiver, f }
+    }
+
+    fn apply(&mut self, result: Result<T, Closed>) -> Result<T, Closed> {
+        if let Ok(value) = &result {
+            (self.f)(value);
+        }
+        result
+    }
+}
+
+receiver_adapter!(InspectReceiver<T, F>, T => Result<T, Closed>, [F: FnMut(&T)]);
+
+/// A [`Sender`] that calls a function with a reference to the value
+/// before sending it. Created by [`Sender::inspect`].
+pub struct InspectSender<T, F> {
+    sender: Sender<T>,
+    f: F,
+}
+
+impl<T, F: FnMut(&T)> InspectSender<T, F> {
+    pub(crate) fn new(sender: Sender<T>, f: F) -> Self {
+        InspectSender { sender, f }
+    }
+
+    /// Sends a message on the channel. Fails if the Receiver is dropped.
+    ///
+    /// The function is not called if the send is known to fail up
+    /// front, but may still be called if the Receiver drops while we
+    /// are sending.
+    pub fn send(&mut self, value: T) -> Result<(), Closed> {
+        if self.sender.can_send() {
+            (self.f)(&value);
+        }
+        self.sender.send(value)
+    }
+}
+
+sender_adapter!(InspectSender<T, F>, T, [F: FnMut(&T)]);
+
+/// A [`Sender`] that applies a function to each value before sending
+/// it. Created by [`Sender::map_input`].
+pub struct MappedSender<T, U, F> {
+    sender: Sender<T>,
+    f: F,
+    _input: PhantomData<fn(U)>,
+}
+
+impl<T, U, F: FnMut(U) -> T> MappedSender<T, U, F> {
+    pub(crate) fn new(sender: Sender<T>, f: F) -> Self {
+        MappedSender {
+            sender,
+            f,
+            _input: PhantomData,
+        }
+    }
+
+    /// Converts and sends a message on the channel. Fails if the
+    /// Receiver is dropped.
+    ///
+    /// The function is not called if the send is known to fail up front.
+    pub fn send(&mut self, value: U) -> Result<(), Closed> {
+        if !self.sender.can_send() {
+            return Err(Closed());
+        }
+        self.sender.send((self.f)(value))
+    }
+}
+
+sender_adapter!(MappedSender<T, U, F>, T, [F: FnMut(U) -> T]);
@@ -0,0 +1,219 @@
+//! An executable specification of the channel protocol.
+//!
+//! [`Model`] is a pure, single-threaded model of one channel carrying a
+//! `u8`. Applying an [`Op`] returns the [`Outcome`] the real channel
+//! reports for the same call, and the model tracks how many times each
+//! side should have been woken. The crate's property tests drive both
+//! side by side, so the model doubles as precise documentation of edge
+//! cases such as dropping a waiting Sender or receiving after a close.
+
+use crate::Closed;
+use core::task::Poll;
+
+/// An operation on one of the channel's handles.
+#[derive(Clone, Copy, Debug)]
+pub enum Op {
+    /// `Sender::send`.
+    Send(u8),
+    /// Polls `Sender::wait`, starting it if the Sender isn't waiting.
+    Wait,
+    /// Drops the Sender, or the `wait` future holding it.
+    DropSender,
+    /// `Receiver::try_recv`.
+    TryRecv,
+    /// Polls the Receiver.
+    Poll,
+    /// Drops the Receiver.
+    DropReceiver,
+}
+
+/// What an operation reported back to the caller.
+#[derive(Debug, Eq, PartialEq)]
+pub enum Outcome {
+    /// The handle the operation needs is gone.
+    Skipped,
+    /// The result of `Sender::send`.
+    Sent(Result<(), Closed>),
+    /// The result of polling `Sender::wait`, without the Sender.
+    Waited(Poll<Result<(), Closed>>),
+    /// The result of polling the Receiver.
+    Received(Poll<Result<u8, Closed>>),
+    /// The result of `Receiver::try_recv`, with `Err(None)` for empty.
+    TryReceived(Result<u8, Option<Closed>>),
+    /// A handle was dropped.
+    Dropped,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum SenderModel {
+    Present { did_send: bool },
+    Waiting { did_send: bool },
+    Gone,
+}
+
+/// Reference model of the channel as observed by a single thread.
+#[derive(Clone, Debug)]
+pub struct Model {
+    sender: SenderModel,
+    receiver: bool,
+    value: Option<u8>,
+    closed: bool,
+    recv_waker: bool,
+    send_waker: bool,
+    recv_wakes: usize,
+    send_wakes: usize,
+}
+
+impl Model {
+    /// A freshly created channel.
+    pub fn new() -> Self {
+        Model {
+            sender: SenderModel::Present { did_send: false },
+            receiver: true,
+            value: None,
+            closed: false,
+            recv_waker: false,
+            send_waker: false,
+            recv_wakes: 0,
+            send_wakes: 0,
+        }
+    }
+
+    /// The number of times the Receiver has been woken.
+    pub fn recv_wakes(&self) -> usize {
+        self.recv_wakes
+    }
+
+    /// The number of times the Sender has been woken.
+    pub fn send_wakes(&self) -> usize {
+        self.send_wakes
+    }
+
+    fn wake_receiver(&mut self) {
+        if self.recv_waker {
+            self.recv_waker = false;
+            self.recv_wakes += 1;
+        }
+    }
+
+    /// The receiver is finished with the channel and wakes a waiting sender.
+    fn finish_receiver(&mut self) {
+        self.receiver = false;
+        if self.send_waker {
+            self.send_waker = false;
+            self.send_wakes += 1;
+        }
+    }
+
+    fn drop_sender(&mut self, did_send: bool) {
+        self.sender = SenderModel::Gone;
+        if !did_send {
+            self.closed = true;
+            self.wake_receiver();
+        }
+    }
+
+    fn take(&mut self) -> Option<Result<u8, Closed>> {
+        if let Some(v) = self.value.take() {
+            Some(Ok(v))
+        } else if self.closed {
+            Some(Err(Closed()))
+        } else {
+            None
+        }
+    }
+
+    /// Applies an operation and returns what the real channel would report.
+    pub fn apply(&mut self, op: Op) -> Outcome {
+        match op {
+            Op::Send(v) => match self.sender {
+                SenderModel::Present { did_send: true } => Outcome::Sent(Err(Closed())),
+                SenderModel::Present { did_send: false } => {
+                    self.sender = SenderModel::Present { did_send: true };
+                    self.value = Some(v);
+                    self.wake_receiver();
+                    Outcome::Sent(if self.closed { Err(Closed()) } else { Ok(()) })
+                }
+                _ => Outcome::Skipped,
+            },
+            Op::Wait => match self.sender {
+                SenderModel::Present { did_send } | SenderModel::Waiting { did_send } => {
+                    if self.closed {
+                        self.send_waker = false;
+                        self.drop_sender(did_send);
+                        Outcome::Waited(Poll::Ready(Err(Closed())))
+                    } else if self.recv_waker {
+                        self.send_waker = false;
+                        self.sender = SenderModel::Present { did_send };
+                        Outcome::Waited(Poll::Ready(Ok(())))
+                    } else {
+                        self.sender = SenderModel::Waiting { did_send };
+                        self.send_waker = true;
+                        Outcome::Waited(Poll::Pending)
+                    }
+                }
+                SenderModel::Gone => Outcome::Skipped,
+            },
+            Op::DropSender => match self.sender {
+                SenderModel::Present { did_send } | SenderModel::Waiting { did_send } => {
+                    // Dropping a wait removes its waker.
+                    self.send_waker = false;
+                    self.drop_sender(did_send);
+                    Outcome::Dropped
+                }
+                SenderModel::Gone => Outcome::Skipped,
+            },
+            Op::TryRecv => {
+                if !self.receiver {
+                    return Outcome::Skipped;
+                }
+                match self.take() {
+                    Some(result) => {
+                        self.finish_receiver();
+                        Outcome::TryReceived(result.map_err(Some))
+                    }
+                    None => Outcome::TryReceived(Err(None)),
+                }
+            }
+            Op::Poll => {
+                if !self.receiver {
+                    return Outcome::Skipped;
+                }
+                match self.take() {
+                    Some(result) => {
+                        self.finish_receiver();
+                        Outcome::Received(Poll::Ready(result))
+                    }
+                    None => {
+                        self.recv_waker = true;
+                        if self.send_waker {
+                            self.send_wakes += 1;
+                        }
+                        Outcome::Received(Poll::Pending)
+                    }
+                }
+            }
+            Op::DropReceiver => {
+                if !self.receiver {
+                    return Outcome::Skipped;
+                }
+                if !self.closed {
+                    self.closed = true;
+                    self.recv_waker = false;
+                    if self.send_waker {
+                        self.send_waker = false;
+                        self.send_wakes += 1;
+                    }
+                }
+                self.finish_receiver();
+                Outcome::Dropped
+            }
+        }
+    }
+}
+
+impl Default for Model {
+    fn default() -> Self {
+        Model::new()
+    }
+}
@@ -7,6 +7,10 @@ use core::cell::UnsafeCell;
 use core::mem::MaybeUninit;
 use core::sync::atomic::{AtomicUsize, Ordering};
 
+/// Total number of spins performed waiting for any mutex.
+#[cfg(feature = "diagnostics")]
+pub(crate) static SPINS: AtomicUsize = AtomicUsize::new(0);
+
 /// A mutex that can be used in no_std environments and internally is
 /// based on spinlocks.
 ///
@@ -35,11 +39,16 @@ impl<T, const PRESENT_BIT: usize, const LOCKED_BIT: usize> Mutex<T, PRESENT_BIT,
     pub(crate) unsafe fn lock<'a>(
         &'a self,
         state: &'a AtomicUsize,
-    ) -> MutexGuard<'_, T, PRESENT_BIT, LOCKED_BIT> {
+    ) -> MutexGuard<'a, T, PRESENT_BIT, LOCKED_BIT> {
         // Try to lock the mutex.
         while state.fetch_or(1 << LOCKED_BIT, Ordering::Acquire) & (1 << LOCKED_BIT) != 0 {
             // If we failed, wait until the mutex is unlocked.
             while state.load(Ordering::Acquire) & (1 << LOCKED_BIT) != 0 {
+                #[cfg(feature = "diagnostics")]
+                SPINS.fetch_add(1, Ordering::Relaxed);
+                #[cfg(feature = "sim")]
+                crate::sim::spin();
+                #[cfg(not(feature = "sim"))]
                 core::hint::spin_loop();
             }
         }
@@ -65,7 +74,9 @@ impl<T, const PRESENT_BIT: usize, const LOCKED_BIT: usize> Mutex<T, PRESENT_BIT,
 
 /// A guard for a held mutex.
 ///
-/// NOTE: The code should never panic while holding this guard!
+/// NOTE: The code should never panic while holding this guard! This
+/// includes waking or dropping a `Waker`, which may run arbitrary code:
+/// take it out of the mutex and release the guard first.
 pub(crate) struct MutexGuard<'a, T, const PRESENT_BIT: usize, const LOCKED_BIT: usize> {
     mutex: &'a Mutex<T, PRESENT_BIT, LOCKED_BIT>,
     state: &'a AtomicUsize,
@@ -94,20 +105,25 @@ impl<'a, T, const PRESENT_BIT: usize, const LOCKED_BIT: usize>
         }
     }
 
-    pub(crate) fn emplace(&mut self, value: T) {
-        if self.state.fetch_or(1 << PRESENT_BIT, Ordering::Relaxed) & (1 << PRESENT_BIT) != 0 {
-            // SAFETY: When the mutex created this guard, it set locked to 1 before and present
-            // bit is set.
-            unsafe {
-                // Value is present already, drop in place
-                core::ptr::drop_in_place((*self.mutex.value.get()).as_mut_ptr());
-            }
-        }
+    /// Stores a value, returning the previous one if present.
+    ///
+    /// The previous value is handed back rather than dropped in place so
+    /// that the caller can drop it after releasing the lock.
+    pub(crate) fn replace(&mut self, value: T) -> Option<T> {
+        let old =
+            if self.state.fetch_or(1 << PRESENT_BIT, Ordering::Relaxed) & (1 << PRESENT_BIT) != 0 {
+                // SAFETY: When the mutex created this guard, it set locked to 1 before and
+                // present bit is set.
+                Some(unsafe { (*self.mutex.value.get()).assume_init_read() })
+            } else {
+                None
+            };
 
         // SAFETY: When the mutex created this guard, it set locked to 1 before.
         unsafe {
             (*self.mutex.value.get()).write(value);
         }
+        old
     }
 }
 
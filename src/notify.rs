@@ -0,0 +1,150 @@
+//! A permit-based notification for waking tasks without sending a value.
+
+use crate::waiters::{next_id, Waiters};
+use core::fmt;
+use core::future::Future;
+use core::pin::Pin;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use core::task::{Context, Poll};
+
+const WAITERS_LOCKED_BIT: usize = 0;
+const WAITERS_PRESENT_BIT: usize = 1;
+const PERMIT_BIT: usize = 2;
+
+/// Notifies a waiting task, or the next one to wait if none is waiting.
+///
+/// Notifying stores a single permit, which the next call to
+/// [`Notify::notified`] consumes. Notifying again before the permit is
+/// consumed has no further effect. Share it between tasks with an `Arc`.
+pub struct Notify {
+    // Carries the state of the waiter list and permit.
+    state: AtomicUsize,
+
+    // Wakers of every task waiting for a permit, oldest first.
+    waiters: Waiters<WAITERS_PRESENT_BIT, WAITERS_LOCKED_BIT>,
+}
+
+impl Notify {
+    /// Creates a new Notify with no permit stored.
+    pub const fn new() -> Self {
+        Notify {
+            state: AtomicUsize::new(0),
+            waiters: Waiters::new(),
+        }
+    }
+
+    /// Stores a permit and wakes the task that has waited longest, if any.
+    pub fn notify_one(&self) {
+        self.state.fetch_or(1 << PERMIT_BIT, Ordering::Release);
+        self.wake_one();
+    }
+
+    /// Consumes the permit if one is stored, without waiting.
+    pub fn try_notified(&self) -> bool {
+        self.state.fetch_and(!(1 << PERMIT_BIT), Ordering::Acquire) & (1 << PERMIT_BIT) != 0
+    }
+
+    /// Waits for a permit and consumes it.
+    pub fn notified(&self) -> Notified<'_> {
+        Notified {
+            notify: self,
+            id: next_id(),
+            registered: false,
+        }
+    }
+
+    fn wake_one(&self) {
+        // SAFETY: The state bits are used only by this list.
+        if let Some(waker) = unsafe { self.waiters.take_first(&self.state) } {
+            crate::inner::wake(&waker);
+        }
+    }
+}
+
+/// A future waiting for a permit. Created by [`Notify::notified`].
+///
+/// Dropping it removes its waker. If it was woken but dropped before it
+/// could take the permit, the next waiting task is woken instead.
+pub struct Notified<'a> {
+    notify: &'a Notify,
+    id: usize,
+    registered: bool,
+}
+
+impl Notified<'_> {
+    fn deregister(&mut self) -> bool {
+        self.registered = false;
+        // SAFETY: The state bits are used only by this list.
+        unsafe { self.notify.waiters.deregister(&self.notify.state, self.id) }
+    }
+}
+
+impl Future for Notified<'_> {
+    type Output = ();
+    fn poll(self: Pin<&mut Self>, ctx: &mut Context) -> Poll<()> {
+        let this = Pin::into_inner(self);
+        if this.notify.try_notified() {
+            this.deregister();
+            return Poll::Ready(());
+        }
+        // SAFETY: The state bits are used only by this list.
+        unsafe {
+            this.notify
+                .waiters
+                .register(&this.notify.state, this.id, ctx.waker())
+        };
+        this.registered = true;
+        // We may have been notified before we registered.
+        if this.notify.try_notified() {
+            this.deregister();
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+impl Drop for Notified<'_> {
+    fn drop(&mut self) {
+        if !self.registered {
+            return;
+        }
+        // If we were woken but never took the permit, pass the wake on.
+        let was_waiting = self.deregister();
+        if !was_waiting && self.notify.state.load(Ordering::Acquire) & (1 << PERMIT_BIT) != 0 {
+            self.notify.wake_one();
+        }
+    }
+}
+
+impl fmt::Debug for Notified<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Notified")
+            .field("notify", self.notify)
+            .finish()
+    }
+}
+
+impl Default for Notify {
+    fn default() -> Self {
+        Notify::new()
+    }
+}
+
+impl Drop for Notify {
+    fn drop(&mut self) {
+        self.waiters.drop(&self.state);
+    }
+}
+
+impl fmt::Debug for Notify {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let permit = self.state.load(Ordering::Acquire) & (1 << PERMIT_BIT) != 0;
+        f.debug_struct("Notify")
+            .field("permit", &permit)
+            .finish_non_exhaustive()
+    }
+}
+
+unsafe impl Send for Notify {}
+unsafe impl Sync for Notify {}
@@ -0,0 +1,112 @@
+//! Weak handles for watching a channel without taking part in it.
+
+use crate::flags::Flags;
+use crate::Inner;
+use alloc::sync::Weak;
+use core::fmt;
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+
+/// A weak handle which can watch a channel but not send or receive on
+/// it. Created by [`Sender::observe`](crate::Sender::observe) or
+/// [`Receiver::observe`](crate::Receiver::observe).
+///
+/// Observers don't keep the channel open or delay it being freed.
+pub struct Observer<T> {
+    inner: Weak<Inner<T>>,
+}
+
+impl<T> Observer<T> {
+    pub(crate) fn new(inner: Weak<Inner<T>>) -> Self {
+        Observer { inner }
+    }
+
+    /// A snapshot of the channel's state word, or `None` if both
+    /// handles have dropped.
+    pub fn flags(&self) -> Option<Flags> {
+        self.inner.upgrade().map(|inner| inner.flags())
+    }
+
+    /// true once the channel is closed, the Receiver has finished with
+    /// it, or both handles have dropped. A true result is final.
+    pub fn is_finished(&self) -> bool {
+        match self.inner.upgrade() {
+            Some(inner) => inner.is_finished(),
+            None => true,
+        }
+    }
+
+    /// Waits for the channel to finish. See [`Observer::is_finished`].
+    pub fn finished(&self) -> Finished<'_, T> {
+        Finished {
+            observer: self,
+            id: crate::waiters::next_id(),
+            registered: false,
+        }
+    }
+}
+
+/// A future waiting for a channel to finish. Created by
+/// [`Observer::finished`].
+///
+/// Dropping it removes its waker from the channel.
+pub struct Finished<'a, T> {
+    observer: &'a Observer<T>,
+    id: usize,
+    registered: bool,
+}
+
+impl<T> Future for Finished<'_, T> {
+    type Output = ();
+    fn poll(self: Pin<&mut Self>, ctx: &mut Context) -> Poll<()> {
+        let this = Pin::into_inner(self);
+        let Some(inner) = this.observer.inner.upgrade() else {
+            return Poll::Ready(());
+        };
+        if inner.is_finished() {
+            return Poll::Ready(());
+        }
+        inner.register_observer(this.id, ctx.waker());
+        this.registered = true;
+        // The channel may have finished before we registered.
+        if inner.is_finished() {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+impl<T> Drop for Finished<'_, T> {
+    fn drop(&mut self) {
+        if !self.registered {
+            return;
+        }
+        if let Some(inner) = self.observer.inner.upgrade() {
+            inner.deregister_observer(self.id);
+        }
+    }
+}
+
+impl<T> fmt::Debug for Finished<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Finished")
+            .field("observer", self.observer)
+            .finish()
+    }
+}
+
+impl<T> Clone for Observer<T> {
+    fn clone(&self) -> Self {
+        Observer::new(self.inner.clone())
+    }
+}
+
+impl<T> fmt::Debug for Observer<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Observer")
+            .field("flags", &self.flags())
+            .finish()
+    }
+}
@@ -0,0 +1,105 @@
+//! Blocking operations for synchronous code, built on thread parking.
+
+use alloc::sync::Arc;
+use core::future::Future;
+use core::pin::pin;
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::task::{Context, Poll, Waker};
+use std::task::Wake;
+use std::thread::{self, Thread};
+
+/// The most times [`block_on`] spins waiting for a wake before parking.
+const DEFAULT_SPIN_LIMIT: u32 = 100;
+
+/// Decides how long to spin before parking, based on how the previous
+/// waits of the same call went.
+///
+/// Spinning keeps ping-pong latency low when the other side is about to
+/// respond, while parking saves the CPU when it is not. A wake that
+/// arrives while spinning doubles the budget, up to the limit, and
+/// having to park halves it.
+struct Backoff {
+    spins: u32,
+    limit: u32,
+}
+
+impl Backoff {
+    fn new(limit: u32) -> Self {
+        Backoff {
+            spins: limit,
+            limit,
+        }
+    }
+
+    fn woke_spinning(&mut self) {
+        self.spins = self.spins.saturating_mul(2).max(1).min(self.limit);
+    }
+
+    fn parked(&mut self) {
+        self.spins /= 2;
+    }
+}
+
+/// Wakes a task by unparking the thread blocked on it.
+struct Unparker {
+    thread: Thread,
+    notified: AtomicBool,
+}
+
+impl Wake for Unparker {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.notified.store(true, Ordering::Release);
+        self.thread.unpark();
+    }
+}
+
+impl Unparker {
+    /// Waits to be woken, spinning for a while before parking.
+    fn wait(&self, backoff: &mut Backoff) {
+        for _ in 0..backoff.spins {
+            if self.notified.swap(false, Ordering::Acquire) {
+                backoff.woke_spinning();
+                return;
+            }
+            core::hint::spin_loop();
+        }
+        backoff.parked();
+        while !self.notified.swap(false, Ordering::Acquire) {
+            thread::park();
+        }
+    }
+}
+
+/// Polls a future on the current thread, spinning then parking between
+/// polls.
+///
+/// This is a minimal executor for sync code that needs to wait on one
+/// of this crate's futures, such as [`Sender::closed`](crate::Sender::closed)
+/// or an [`Exchanger`](crate::exchange::Exchanger), without choosing a
+/// runtime. It does not drive timers or I/O.
+pub fn block_on<F: Future>(fut: F) -> F::Output {
+    block_on_spinning(fut, DEFAULT_SPIN_LIMIT)
+}
+
+/// Like [`block_on`], but spins at most `max_spins` times waiting for a
+/// wake before parking. Zero parks immediately.
+pub fn block_on_spinning<F: Future>(fut: F, max_spins: u32) -> F::Output {
+    let mut fut = pin!(fut);
+    let mut backoff = Backoff::new(max_spins);
+    let unparker = Arc::new(Unparker {
+        thread: thread::current(),
+        notified: AtomicBool::new(false),
+    });
+    let waker = Waker::from(unparker.clone());
+    let mut ctx = Context::from_waker(&waker);
+    loop {
+        match fut.as_mut().poll(&mut ctx) {
+            Poll::Ready(v) => return v,
+            Poll::Pending => unparker.wait(&mut backoff),
+        }
+    }
+}
@@ -1,94 +1,365 @@
 use crate::inner::InnerValue;
+use crate::timer::{timeout_at, TimeoutAt, Timer};
 use crate::*;
 use core::task::{Context, Poll};
 use core::{future::Future, pin::Pin};
 
 /// The receiving half of a oneshot channel.
-#[derive(Debug)]
+///
+/// With the `repr-c` feature the layout is `#[repr(C)]`, starting with
+/// a pointer to the channel's shared state: the address returned by
+/// [`Receiver::into_raw`] with its low bit cleared.
+#[cfg_attr(feature = "repr-c", repr(C))]
 pub struct Receiver<T> {
-    inner: Arc<Inner<T>>,
+    inner: Shared<T>,
     did_receive: bool,
+    spins: u32,
+    #[cfg(feature = "diagnostics")]
+    polls: usize,
 }
 
+#[cfg(feature = "repr-c")]
+const _: () = assert!(core::mem::offset_of!(Receiver<()>, inner) == 0);
+
 impl<T> Receiver<T> {
-    pub(crate) fn new(inner: Arc<Inner<T>>) -> Self {
+    pub(crate) fn new(inner: Shared<T>) -> Self {
         Receiver {
             inner,
             did_receive: false,
+            spins: 0,
+            #[cfg(feature = "diagnostics")]
+            polls: 0,
         }
     }
 
     /// Closes the channel by causing an immediate drop.
     pub fn close(self) {}
 
+    /// true if the Sender has dropped without sending.
+    ///
+    /// This is a single atomic load and takes no locks.
+    ///
+    /// NOTE: The result may be instantly be out of date if it returns
+    /// false. A true result is final.
+    pub fn is_closed(&self) -> bool {
+        self.inner.is_closed()
+    }
+
+    /// true if the Sender is currently waiting for us in
+    /// [`Sender::wait`](crate::Sender::wait).
+    ///
+    /// Together with [`Receiver::try_recv`] this lets a poller tell "empty"
+    /// apart from "empty, but the Sender is ready to produce a value".
+    ///
+    /// NOTE: The result may be instantly be out of date.
+    pub fn sender_is_waiting(&self) -> bool {
+        self.inner.flags().contains(flags::SEND_WAITING)
+    }
+
+    /// An upper bound on the number of other live references to the
+    /// channel: at least 1 while the Sender is alive, and 0 once it has
+    /// dropped and nothing else refers to the channel.
+    ///
+    /// This is the channel's reference count, so it also includes the
+    /// future returned by [`Sender::closing`] until it completes, and
+    /// briefly an observer checking on the channel.
+    ///
+    /// A Sender that was leaked with `mem::forget` or converted with
+    /// `into_raw` still counts, so a nonzero count on a channel that
+    /// never completes can point at a leaked handle.
+    pub fn peer_handle_count(&self) -> usize {
+        self.inner.strong_count() - 1
+    }
+
+    /// Creates a weak handle for watching the channel, which doesn't
+    /// keep it open.
+    #[cfg(feature = "observer")]
+    pub fn observe(&self) -> Observer<T> {
+        Observer::new(self.inner.downgrade())
+    }
+
+    /// A snapshot of the channel's state word, for inspection and
+    /// debugging. See the [`flags`](crate::flags) module for the layout.
+    ///
+    /// NOTE: The snapshot may be out of date as soon as it is taken.
+    pub fn flags(&self) -> crate::flags::Flags {
+        self.inner.flags()
+    }
+
+    /// Converts the Receiver into a raw pointer, for storing in intrusive
+    /// structures or checkpointing a task. The low bit of the pointer
+    /// records whether this Receiver has already finished with the channel.
+    ///
+    /// The pointer must be converted back with [`Receiver::from_raw`] or the
+    /// channel will never be freed.
+    ///
+    /// Only the pointer and that bit survive the round trip: the spin
+    /// count set with [`Receiver::set_spins`] and, with the `diagnostics`
+    /// feature, the poll count are reset to zero.
+    pub fn into_raw(self) -> *const () {
+        let this = core::mem::ManuallyDrop::new(self);
+        // SAFETY: `this` is never dropped, so the reference is moved out exactly once.
+        let inner = unsafe { core::ptr::read(&this.inner) };
+        inner
+            .into_raw()
+            .cast::<u8>()
+            .wrapping_add(this.did_receive as usize)
+            .cast::<()>()
+    }
+
+    /// Reconstitutes a Receiver from a pointer returned by [`Receiver::into_raw`].
+    ///
+    /// # Safety
+    ///
+    /// `raw` must have come from `Receiver::<T>::into_raw` for the same `T`,
+    /// and must be converted back at most once.
+    pub unsafe fn from_raw(raw: *const ()) -> Self {
+        let tag = raw as usize & 1;
+        let inner = Shared::from_raw(raw.cast::<u8>().wrapping_sub(tag).cast::<Inner<T>>());
+        let did_receive = tag != 0;
+        let mut this = Receiver::new(inner);
+        this.did_receive = did_receive;
+        this
+    }
+
+    /// Sets how many extra times polling checks for a value, spinning
+    /// in between, before registering a waker. This can cut the latency
+    /// of short handoffs between threads, where the round trip through a
+    /// wake dominates, at the cost of burning CPU. The default is zero.
+    pub fn set_spins(&mut self, spins: u32) {
+        self.spins = spins;
+    }
+
+    /// Attaches a priority hint to our waker, for the Sender to read
+    /// with [`Sender::receiver_priority`] before waking us.
+    #[cfg(feature = "priority")]
+    pub fn set_priority(&self, priority: u8) {
+        self.inner
+            .recv_priority
+            .store(priority, core::sync::atomic::Ordering::Release);
+    }
+
+    /// The priority hint the Sender attached to its waker.
+    #[cfg(feature = "priority")]
+    pub fn sender_priority(&self) -> u8 {
+        self.inner
+            .send_priority
+            .load(core::sync::atomic::Ordering::Acquire)
+    }
+
+    /// The number of times this receiver has been polled.
+    #[cfg(feature = "diagnostics")]
+    pub fn polls(&self) -> usize {
+        self.polls
+    }
+
+    /// The number of times the sender has woken this receiver.
+    #[cfg(feature = "diagnostics")]
+    pub fn wakes(&self) -> usize {
+        self.inner.recv_wakes()
+    }
+
     /// Attempts to receive. On failure, if the channel is not closed,
     /// returns self to try again.
     pub fn try_recv(mut self) -> Result<T, TryRecvError<T>> {
-        match self.inner.try_take() {
-            InnerValue::Present(v) => {
-                self.did_receive = true;
-                Ok(v)
-            }
-            InnerValue::Pending => Err(TryRecvError::Empty(self)),
-            InnerValue::Closed => {
-                self.did_receive = true;
-                Err(TryRecvError::Closed)
-            }
+        match self.take() {
+            Some(Ok(v)) => Ok(v),
+            Some(Err(Closed())) => Err(TryRecvError::Closed),
+            None => Err(TryRecvError::Empty(self)),
         }
     }
+
+    /// Calls `f` with a reference to the value if it has been sent,
+    /// leaving it in place to be received later.
+    pub fn with_value<R>(&mut self, f: impl FnOnce(&T) -> R) -> Option<R> {
+        // SAFETY: We are the receiver and hold it exclusively while the
+        // value is borrowed, so it can be neither taken nor shared.
+        unsafe { self.inner.peek() }.map(f)
+    }
+
+    /// Blocks the current thread until a message is received or the
+    /// channel is closed.
+    #[cfg(feature = "parking")]
+    pub fn recv_blocking(self) -> Result<T, Closed> {
+        crate::parking::block_on(self)
+    }
+
+    /// Receives, giving up once `deadline` has passed on `timer`.
+    ///
+    /// The receiver is borrowed so it can be used again after a timeout.
+    pub fn recv_timeout_at<'a, Tm: Timer>(
+        &'a mut self,
+        timer: &'a Tm,
+        deadline: Tm::Instant,
+    ) -> TimeoutAt<'a, &'a mut Self, Tm> {
+        timeout_at(self, timer, deadline)
+    }
+
+    /// Receives, tagging any error with `tag` to identify the channel.
+    pub async fn recv_tagged<K>(self, tag: K) -> Result<T, TaggedClosed<K>> {
+        self.await.map_err(|closed| closed.with_tag(tag))
+    }
+
+    /// Receives, falling back to `T::default()` if the Sender drops
+    /// without sending.
+    pub fn recv_or_default(self) -> impl Future<Output = T>
+    where
+        T: Default,
+    {
+        self.recv_or_else(T::default)
+    }
+
+    /// Receives, falling back to the result of `f` if the Sender drops
+    /// without sending.
+    pub async fn recv_or_else<F: FnOnce() -> T>(self, f: F) -> T {
+        self.await.unwrap_or_else(|Closed()| f())
+    }
+
+    /// Moves the waker registered by polling this Receiver over to
+    /// `other`, so the waiting task is woken by `other`'s Sender without
+    /// having to poll it first. Returns false if no waker was registered.
+    pub fn transfer_waiting_to<U>(&mut self, other: &mut Receiver<U>) -> bool {
+        let mut recv_lock = self.inner.lock_recv();
+        let waker = recv_lock.take();
+        drop(recv_lock);
+        let Some(waker) = waker else {
+            return false;
+        };
+
+        let mut other_lock = other.inner.lock_recv();
+        let old_waker = other_lock.replace(waker.clone());
+        drop(other_lock);
+        drop(old_waker);
+
+        // The other channel may have completed before we registered.
+        let state = other.inner.flags();
+        if state.contains(flags::VALUE_PRESENT) || state.contains(flags::CLOSED) {
+            other.inner.wake_recv(&waker);
+            return true;
+        }
+
+        // As when polling, let a waiting sender know we are waiting.
+        other.inner.wake_waiting_sender();
+        true
+    }
+
+    /// Creates a receiver which applies `f` to the value as it is received.
+    pub fn map<U, F: FnMut(T) -> U>(self, f: F) -> MappedReceiver<T, U, F> {
+        MappedReceiver::new(self, f)
+    }
+
+    /// Creates a receiver which calls `f` with a reference to the value
+    /// as it is received.
+    pub fn inspect<F: FnMut(&T)>(self, f: F) -> InspectReceiver<T, F> {
+        InspectReceiver::new(self, f)
+    }
+
+    /// true once a result has been returned.
+    #[cfg(feature = "futures-core")]
+    pub(crate) fn is_terminated(&self) -> bool {
+        self.did_receive
+    }
+
+    /// Attempts to take the value without consuming the receiver.
+    ///
+    /// Returns `None` if the channel is still empty. Once a result has
+    /// been returned, all further calls report the channel as closed.
+    pub(crate) fn take(&mut self) -> Option<Result<T, Closed>> {
+        if self.did_receive {
+            return Some(Err(Closed()));
+        }
+        let result = take_value(&self.inner, &mut self.did_receive)?;
+        self.inner.wake_finished();
+        Some(result)
+    }
+}
+
+/// Like `Receiver::take`, but leaves the caller to notify the sender that
+/// we are finished.
+fn take_value<T>(inner: &Inner<T>, did_receive: &mut bool) -> Option<Result<T, Closed>> {
+    if *did_receive {
+        return Some(Err(Closed()));
+    }
+    let result = match inner.try_take() {
+        InnerValue::Present(v) => Ok(v),
+        InnerValue::Pending => return None,
+        InnerValue::Closed => Err(Closed()),
+    };
+    *did_receive = true;
+    Some(result)
+}
+
+impl<T, E: From<Closed>> Receiver<Result<T, E>> {
+    /// Creates a receiver for a fallible payload which reports the
+    /// channel closing through the payload's own error type.
+    pub fn flatten(self) -> FlattenReceiver<T, E> {
+        FlattenReceiver::new(self)
+    }
 }
 
 impl<T> Future for Receiver<T> {
     type Output = Result<T, Closed>;
     fn poll(self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Result<T, Closed>> {
         let this = Pin::into_inner(self);
+        #[cfg(feature = "tracing")]
+        let _span = tracing::trace_span!(
+            "receive",
+            channel = this.inner.id(),
+            label = this.inner.label()
+        )
+        .entered();
+        #[cfg(feature = "diagnostics")]
+        {
+            this.polls += 1;
+        }
 
         // Attempt lock free take - this makes it substantially faster when
         // highly contended.
-        match this.inner.try_take() {
-            InnerValue::Present(v) => {
-                this.did_receive = true;
-                return Poll::Ready(Ok(v));
-            }
-            InnerValue::Pending => {}
-            InnerValue::Closed => {
-                this.did_receive = true;
-                return Poll::Ready(Err(Closed()));
+        if let Some(result) = this.take() {
+            return Poll::Ready(result);
+        }
+        for _ in 0..this.spins {
+            #[cfg(feature = "sim")]
+            crate::sim::spin();
+            #[cfg(not(feature = "sim"))]
+            core::hint::spin_loop();
+            if let Some(result) = this.take() {
+                return Poll::Ready(result);
             }
-        };
+        }
 
         // No value yet, register a waker
         let mut recv_lock = this.inner.lock_recv();
 
         // Attempt to take value - we now have a lock on the receiver
-        match this.inner.try_take() {
-            InnerValue::Present(v) => {
-                this.did_receive = true;
-                return Poll::Ready(Ok(v));
-            }
-            InnerValue::Pending => {}
-            InnerValue::Closed => {
-                this.did_receive = true;
-                return Poll::Ready(Err(Closed()));
-            }
-        };
+        if let Some(result) = take_value(&this.inner, &mut this.did_receive) {
+            drop(recv_lock);
+            this.inner.wake_finished();
+            return Poll::Ready(result);
+        }
 
-        recv_lock.emplace(ctx.waker().clone());
+        let old_waker = recv_lock.replace(ctx.waker().clone());
 
         // Drop the lock, waker has been registered and we will always return
-        // pending now
+        // pending now. The old waker is dropped outside the lock since its
+        // destructor may run arbitrary code.
         drop(recv_lock);
+        drop(old_waker);
 
-        // If set, notify the sender that we are waiting
-        let send_lock = this.inner.lock_send();
-        if let Some(send_waker) = send_lock.get() {
-            send_waker.wake_by_ref();
-        }
+        // If the sender is waiting for us to wait, notify it
+        this.inner.wake_waiting_sender();
 
         Poll::Pending
     }
 }
 
+#[cfg(feature = "futures-core")]
+impl<T> futures_core::FusedFuture for Receiver<T> {
+    fn is_terminated(&self) -> bool {
+        Receiver::is_terminated(self)
+    }
+}
+
 impl<T> Drop for Receiver<T> {
     fn drop(&mut self) {
         // Mark as closed, and if it wasn't closed already perform cleanup and notify
@@ -99,15 +370,30 @@ impl<T> Drop for Receiver<T> {
             // Make sure to remove the waker we registered - the sender uses it to determine
             // if we are waiting.
             let mut recv_lock = self.inner.lock_recv();
-            recv_lock.take();
+            let recv_waker = recv_lock.take();
             drop(recv_lock);
+            drop(recv_waker);
 
             // Since the channel is now marked as closed, we try to wake the sender
             // if it is waiting.
             let mut send_lock = self.inner.lock_send();
-            if let Some(sender) = send_lock.take() {
-                sender.wake();
+            let send_waker = send_lock.take();
+            drop(send_lock);
+            if let Some(sender) = send_waker {
+                self.inner.wake_send(&sender);
             }
         }
+        self.inner.finish_recv();
+    }
+}
+
+impl<T> core::fmt::Debug for Receiver<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let state = self.inner.flags();
+        f.debug_struct("Receiver")
+            .field("state", &state.describe())
+            .field("sender_waiting", &state.contains(flags::SEND_PRESENT))
+            .field("did_receive", &self.did_receive)
+            .finish()
     }
 }
@@ -0,0 +1,242 @@
+//! Waiting on many receivers at once.
+//!
+//! A [`Selector`] gives each receiver it holds its own waker. When a
+//! Sender wakes one of them, the receiver's id is pushed onto a shared
+//! ready queue, so the selector only ever polls the receivers that have
+//! something to report rather than all of them.
+//!
+//! Ids are reused, so each waker also carries the generation of the
+//! receiver it was made for. A late wake for a receiver which has since
+//! completed or been removed is ignored rather than polling whichever
+//! receiver now has its id.
+
+use crate::mutex::Mutex;
+use crate::{Closed, Receiver};
+use alloc::sync::Arc;
+use alloc::task::Wake;
+use alloc::vec::Vec;
+use core::fmt;
+use core::future::{poll_fn, Future};
+use core::pin::Pin;
+use core::sync::atomic::AtomicUsize;
+use core::task::{Context, Poll, Waker};
+
+const READY_LOCKED_BIT: usize = 0;
+const READY_PRESENT_BIT: usize = 1;
+const WAITER_LOCKED_BIT: usize = 2;
+const WAITER_PRESENT_BIT: usize = 3;
+
+/// Identifies one receiver over the life of a selector: its id and the
+/// generation it was inserted with.
+type Key = (usize, usize);
+
+/// The keys of receivers which have been woken, shared with their wakers.
+struct Queue {
+    // Carries the state of the mutexes.
+    state: AtomicUsize,
+
+    // Keys of receivers which have been woken since the selector last looked.
+    ready: Mutex<Vec<Key>, READY_PRESENT_BIT, READY_LOCKED_BIT>,
+
+    // Waker for the task polling the selector.
+    waiter: Mutex<Waker, WAITER_PRESENT_BIT, WAITER_LOCKED_BIT>,
+}
+
+impl Queue {
+    fn new() -> Self {
+        Queue {
+            state: AtomicUsize::new(0),
+            ready: Mutex::new(),
+            waiter: Mutex::new(),
+        }
+    }
+
+    /// Marks a receiver as ready and wakes the selector.
+    fn push(&self, key: Key) {
+        // SAFETY: The state bits are used only by this mutex.
+        let mut ready_lock = unsafe { self.ready.lock(&self.state) };
+        let mut ready = ready_lock.take().unwrap_or_default();
+        ready.push(key);
+        ready_lock.replace(ready);
+        drop(ready_lock);
+
+        // SAFETY: The state bits are used only by this mutex.
+        let mut waiter_lock = unsafe { self.waiter.lock(&self.state) };
+        let waiter = waiter_lock.take();
+        drop(waiter_lock);
+        if let Some(waiter) = waiter {
+            crate::inner::wake(&waiter);
+        }
+    }
+
+    /// Takes every key marked ready so far.
+    fn take(&self) -> Vec<Key> {
+        // SAFETY: The state bits are used only by this mutex.
+        let mut ready_lock = unsafe { self.ready.lock(&self.state) };
+        let ready = ready_lock.take();
+        drop(ready_lock);
+        ready.unwrap_or_default()
+    }
+
+    /// Registers the selector's waker.
+    fn register(&self, waker: &Waker) {
+        // SAFETY: The state bits are used only by this mutex.
+        let mut waiter_lock = unsafe { self.waiter.lock(&self.state) };
+        let old_waker = waiter_lock.replace(waker.clone());
+        drop(waiter_lock);
+        drop(old_waker);
+    }
+}
+
+impl Drop for Queue {
+    fn drop(&mut self) {
+        self.ready.drop(&self.state);
+        self.waiter.drop(&self.state);
+    }
+}
+
+unsafe impl Send for Queue {}
+unsafe impl Sync for Queue {}
+
+/// The waker given to a single receiver.
+struct Token {
+    key: Key,
+    queue: Arc<Queue>,
+}
+
+impl Wake for Token {
+    fn wake(self: Arc<Self>) {
+        self.queue.push(self.key);
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.queue.push(self.key);
+    }
+}
+
+/// A set of receivers which can be waited on together.
+pub struct Selector<T> {
+    entries: Vec<Option<(Receiver<T>, Waker, usize)>>,
+    free: Vec<usize>,
+    pending: Vec<Key>,
+    queue: Arc<Queue>,
+    len: usize,
+    generation: usize,
+}
+
+impl<T> Selector<T> {
+    /// Creates an empty selector.
+    pub fn new() -> Self {
+        Selector {
+            entries: Vec::new(),
+            free: Vec::new(),
+            pending: Vec::new(),
+            queue: Arc::new(Queue::new()),
+            len: 0,
+            generation: 0,
+        }
+    }
+
+    /// Adds a receiver, returning the id it will be reported under.
+    ///
+    /// Ids of receivers which have completed or been removed are reused.
+    pub fn insert(&mut self, receiver: Receiver<T>) -> usize {
+        let id = self.free.pop().unwrap_or(self.entries.len());
+        let generation = self.generation;
+        self.generation = self.generation.wrapping_add(1);
+        let waker = Waker::from(Arc::new(Token {
+            key: (id, generation),
+            queue: self.queue.clone(),
+        }));
+        if id == self.entries.len() {
+            self.entries.push(Some((receiver, waker, generation)));
+        } else {
+            self.entries[id] = Some((receiver, waker, generation));
+        }
+        self.len += 1;
+        // Poll it once to register its waker.
+        self.pending.push((id, generation));
+        id
+    }
+
+    /// Removes a receiver without waiting for it.
+    pub fn remove(&mut self, id: usize) -> Option<Receiver<T>> {
+        let (receiver, _, _) = self.entries.get_mut(id)?.take()?;
+        self.free.push(id);
+        self.len -= 1;
+        Some(receiver)
+    }
+
+    /// The number of receivers still waiting.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// true if there are no receivers left to wait on.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Waits for the next receiver to complete, returning its id and
+    /// result. Returns `None` once no receivers are left.
+    pub fn select(&mut self) -> impl Future<Output = Option<(usize, Result<T, Closed>)>> + '_ {
+        poll_fn(move |ctx| self.poll_next(ctx))
+    }
+
+    /// Polls for the next receiver to complete.
+    pub fn poll_next(&mut self, ctx: &mut Context) -> Poll<Option<(usize, Result<T, Closed>)>> {
+        loop {
+            if self.is_empty() {
+                return Poll::Ready(None);
+            }
+            let Some((id, generation)) = self.pending.pop() else {
+                self.pending = self.queue.take();
+                if self.pending.is_empty() {
+                    self.queue.register(ctx.waker());
+                    // A receiver may have been woken before we registered.
+                    self.pending = self.queue.take();
+                    if self.pending.is_empty() {
+                        return Poll::Pending;
+                    }
+                }
+                continue;
+            };
+            // Stale keys for completed or removed receivers are skipped.
+            let Some(Some((receiver, waker, current))) = self.entries.get_mut(id) else {
+                continue;
+            };
+            if *current != generation {
+                continue;
+            }
+            let mut receiver_ctx = Context::from_waker(waker);
+            if let Poll::Ready(result) = Pin::new(receiver).poll(&mut receiver_ctx) {
+                self.entries[id] = None;
+                self.free.push(id);
+                self.len -= 1;
+                return Poll::Ready(Some((id, result)));
+            }
+        }
+    }
+}
+
+impl<T> Default for Selector<T> {
+    fn default() -> Self {
+        Selector::new()
+    }
+}
+
+#[cfg(feature = "futures-core")]
+impl<T> futures_core::Stream for Selector<T> {
+    type Item = (usize, Result<T, Closed>);
+    fn poll_next(self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Option<Self::Item>> {
+        Selector::poll_next(Pin::into_inner(self), ctx)
+    }
+}
+
+impl<T> fmt::Debug for Selector<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Selector")
+            .field("len", &self.len)
+            .finish_non_exhaustive()
+    }
+}
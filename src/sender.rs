@@ -1,20 +1,34 @@
+use crate::timer::Timer;
 use crate::*;
-use alloc::sync::Arc;
+use alloc::boxed::Box;
+use alloc::vec::Vec;
 use core::future::{poll_fn, Future};
-use core::task::Poll;
+use core::pin::Pin;
+use core::task::{Context, Poll, Waker};
 
 /// The sending half of a oneshot channel.
-#[derive(Debug)]
+///
+/// With the `repr-c` feature the layout is `#[repr(C)]`, starting with
+/// a pointer to the channel's shared state: the address returned by
+/// [`Sender::into_raw`] with its low bit cleared.
+#[cfg_attr(feature = "repr-c", repr(C))]
 pub struct Sender<T> {
-    inner: Arc<Inner<T>>,
+    inner: Shared<T>,
     did_send: bool,
+    #[cfg(feature = "diagnostics")]
+    polls: usize,
 }
 
+#[cfg(feature = "repr-c")]
+const _: () = assert!(core::mem::offset_of!(Sender<()>, inner) == 0);
+
 impl<T> Sender<T> {
-    pub(crate) fn new(inner: Arc<Inner<T>>) -> Self {
+    pub(crate) fn new(inner: Shared<T>) -> Self {
         Sender {
             inner,
             did_send: false,
+            #[cfg(feature = "diagnostics")]
+            polls: 0,
         }
     }
 
@@ -23,84 +37,533 @@ impl<T> Sender<T> {
 
     /// true if the channel is closed
     ///
-    /// NOTE: This performs an atomic load, but the result may be
-    /// instantly be out of date if it returns false.
+    /// This is a single atomic load and takes no locks, so it is cheap
+    /// enough to check before doing expensive work to produce a value.
+    ///
+    /// NOTE: The result may be instantly be out of date if it returns
+    /// false. A true result is final.
     pub fn is_closed(&self) -> bool {
         self.inner.is_closed()
     }
 
+    /// An upper bound on the number of other live references to the
+    /// channel: at least 1 while the Receiver is alive, and 0 once it has
+    /// dropped and nothing else refers to the channel.
+    ///
+    /// This is the channel's reference count, so it briefly includes an
+    /// observer checking on the channel too.
+    ///
+    /// A Receiver that was leaked with `mem::forget` or converted with
+    /// `into_raw` still counts, so a nonzero count on a channel that
+    /// never completes can point at a leaked handle.
+    pub fn peer_handle_count(&self) -> usize {
+        self.inner.strong_count() - 1
+    }
+
+    /// Creates a weak handle for watching the channel, which doesn't
+    /// keep it open.
+    #[cfg(feature = "observer")]
+    pub fn observe(&self) -> Observer<T> {
+        Observer::new(self.inner.downgrade())
+    }
+
+    /// A snapshot of the channel's state word, for inspection and
+    /// debugging. See the [`flags`](crate::flags) module for the layout.
+    ///
+    /// NOTE: The snapshot may be out of date as soon as it is taken.
+    pub fn flags(&self) -> crate::flags::Flags {
+        self.inner.flags()
+    }
+
+    /// Converts the Sender into a raw pointer, for storing in intrusive
+    /// structures or checkpointing a task. The low bit of the pointer
+    /// records whether this Sender has already finished with the channel.
+    ///
+    /// The pointer must be converted back with [`Sender::from_raw`] or the
+    /// channel will never be freed.
+    ///
+    /// Only the pointer and that bit survive the round trip: with the
+    /// `diagnostics` feature, the poll count is reset to zero.
+    pub fn into_raw(self) -> *const () {
+        let this = core::mem::ManuallyDrop::new(self);
+        // SAFETY: `this` is never dropped, so the reference is moved out exactly once.
+        let inner = unsafe { core::ptr::read(&this.inner) };
+        inner
+            .into_raw()
+            .cast::<u8>()
+            .wrapping_add(this.did_send as usize)
+            .cast::<()>()
+    }
+
+    /// Reconstitutes a Sender from a pointer returned by [`Sender::into_raw`].
+    ///
+    /// # Safety
+    ///
+    /// `raw` must have come from `Sender::<T>::into_raw` for the same `T`,
+    /// and must be converted back at most once.
+    pub unsafe fn from_raw(raw: *const ()) -> Self {
+        let tag = raw as usize & 1;
+        let inner = Shared::from_raw(raw.cast::<u8>().wrapping_sub(tag).cast::<Inner<T>>());
+        let did_send = tag != 0;
+        let mut this = Sender::new(inner);
+        this.did_send = did_send;
+        this
+    }
+
     /// Waits for a Receiver to be waiting for us to send something
     /// (i.e. allows you to produce a value to send on demand).
     /// Fails if the Receiver is dropped.
-    pub fn wait(self) -> impl Future<Output = Result<Self, Closed>> {
-        let mut fut_state = Some(self);
-        poll_fn(move |ctx| {
-            let this = fut_state.take().unwrap();
+    pub fn wait(self) -> Wait<T> {
+        Wait { sender: Some(self) }
+    }
 
-            // Attempt lock free check
-            if this.is_closed() {
+    /// Sends a message once `deadline` has passed on `timer`. Fails
+    /// early if the Receiver goes away first.
+    pub fn send_at<'a, Tm: Timer>(
+        mut self,
+        value: T,
+        timer: &'a Tm,
+        deadline: Tm::Instant,
+    ) -> impl Future<Output = Result<(), Closed>> + 'a
+    where
+        T: 'a,
+    {
+        let mut value = Some(value);
+        poll_fn(move |ctx| {
+            if timer.now() >= deadline {
+                return Poll::Ready(self.send(value.take().unwrap()));
+            }
+            if self.poll_closed(ctx).is_ready() {
                 return Poll::Ready(Err(Closed()));
             }
+            timer.register_wake_at(deadline, ctx.waker());
+            Poll::Pending
+        })
+    }
 
-            let recv_lock = this.inner.lock_recv();
-            if recv_lock.get().is_some() {
-                drop(recv_lock);
+    /// Closes the channel (unless we already sent a message) and waits
+    /// for the Receiver to finish with it: to take the message, observe
+    /// the closure or drop.
+    ///
+    /// This is useful for sequencing protocol shutdown.
+    pub fn closing(self) -> impl Future<Output = ()> {
+        let inner = self.inner.clone();
 
-                // A receiver is waiting for us
-                fut_state = None;
-                return Poll::Ready(Ok(this));
+        // Dropping closes the channel and wakes the receiver.
+        drop(self);
+        poll_fn(move |ctx| {
+            if inner.is_recv_done() {
+                return Poll::Ready(());
             }
 
-            // Keep the receiver locked while we set a waker
-            let mut send_lock = this.inner.lock_send();
-            send_lock.emplace(ctx.waker().clone());
-
-            // Drop both locks, we have a waker registered now
+            let mut send_lock = inner.lock_send();
+            let old_waker = send_lock.replace(ctx.waker().clone());
             drop(send_lock);
-            drop(recv_lock);
+            drop(old_waker);
 
-            fut_state = Some(this);
-            Poll::Pending
+            // The receiver may have finished before we registered.
+            if inner.is_recv_done() {
+                Poll::Ready(())
+            } else {
+                Poll::Pending
+            }
         })
     }
 
+    /// Blocks the current thread until a Receiver is waiting for us to
+    /// send something. Fails if the Receiver is dropped.
+    ///
+    /// NOTE: Only an asynchronous receive counts as waiting, so this
+    /// never returns if the Receiver is only ever used with `try_recv`.
+    #[cfg(feature = "parking")]
+    pub fn wait_blocking(self) -> Result<Self, Closed> {
+        crate::parking::block_on(self.wait())
+    }
+
+    /// Attaches a priority hint to our waker, for the Receiver to read
+    /// with [`Receiver::sender_priority`] before waking us.
+    #[cfg(feature = "priority")]
+    pub fn set_priority(&self, priority: u8) {
+        self.inner
+            .send_priority
+            .store(priority, core::sync::atomic::Ordering::Release);
+    }
+
+    /// The priority hint the Receiver attached to its waker.
+    ///
+    /// Combine with [`Sender::send_deferred`] to boost the receiving
+    /// task before waking it.
+    #[cfg(feature = "priority")]
+    pub fn receiver_priority(&self) -> u8 {
+        self.inner
+            .recv_priority
+            .load(core::sync::atomic::Ordering::Acquire)
+    }
+
+    /// The number of times the future returned by [`Sender::wait`]
+    /// has been polled.
+    #[cfg(feature = "diagnostics")]
+    pub fn polls(&self) -> usize {
+        self.polls
+    }
+
+    /// The number of times the receiver has woken this sender.
+    #[cfg(feature = "diagnostics")]
+    pub fn wakes(&self) -> usize {
+        self.inner.send_wakes()
+    }
+
+    /// Creates a sender which calls `f` with a reference to each value
+    /// before it is sent.
+    pub fn inspect<F: FnMut(&T)>(self, f: F) -> InspectSender<T, F> {
+        InspectSender::new(self, f)
+    }
+
+    /// Creates a sender which accepts `U` and converts each value with
+    /// `f` before it is sent.
+    pub fn map_input<U, F: FnMut(U) -> T>(self, f: F) -> MappedSender<T, U, F> {
+        MappedSender::new(self, f)
+    }
+
+    /// Converts this sender into a single-use completion object for
+    /// callback-based APIs.
+    pub fn into_completer(self) -> Completer<T> {
+        Completer(self)
+    }
+
+    /// Waits for the Receiver to go away, either by dropping or by
+    /// receiving a value. Useful for abandoning expensive work to
+    /// produce a value nobody will receive.
+    pub fn closed(&mut self) -> WaitClosed<'_, T> {
+        WaitClosed { sender: self }
+    }
+
+    /// Polls for the Receiver going away, registering to be woken when it
+    /// does.
+    pub(crate) fn poll_closed(&mut self, ctx: &mut Context) -> Poll<()> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::trace_span!(
+            "closed",
+            channel = self.inner.id(),
+            label = self.inner.label()
+        )
+        .entered();
+        if self.receiver_gone() {
+            return Poll::Ready(());
+        }
+
+        let mut send_lock = self.inner.lock_send();
+        let old_waker = send_lock.replace(ctx.waker().clone());
+        drop(send_lock);
+        drop(old_waker);
+
+        // The receiver may have gone before we registered.
+        if self.receiver_gone() {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+
+    /// Removes the waker left by a finished or abandoned `wait`, so the
+    /// Receiver neither wakes it nor reports us as waiting.
+    fn stop_waiting(&self) {
+        self.inner.set_send_waiting(false);
+        self.clear_send_waker();
+    }
+
+    /// Removes any waker we left in the send slot, so the Receiver won't
+    /// wake a task which is no longer waiting on us.
+    fn clear_send_waker(&self) {
+        // Only we register in the send slot, so if it is empty now it
+        // stays empty.
+        if !self.inner.flags().contains(flags::SEND_PRESENT) {
+            return;
+        }
+        let mut send_lock = self.inner.lock_send();
+        let old_waker = send_lock.take();
+        drop(send_lock);
+        drop(old_waker);
+    }
+
+    fn receiver_gone(&self) -> bool {
+        self.is_closed() || self.inner.is_recv_done()
+    }
+
+    /// true if a call to `send` would currently succeed.
+    pub(crate) fn can_send(&self) -> bool {
+        !self.did_send && !self.is_closed()
+    }
+
     /// Sends a message on the channel. Fails if the Receiver is dropped.
     pub fn send(&mut self, value: T) -> Result<(), Closed> {
+        // Attempt to wake up a receiver
+        if let Some(waker) = self.emplace(value)? {
+            self.inner.wake_recv(&waker);
+        }
+        self.check_closed()
+    }
+
+    /// Sends a message on the channel, handing the message back if the
+    /// Receiver dropped without receiving it or a message was already sent.
+    pub fn try_send(&mut self, value: T) -> Result<(), SendError<T>> {
         if self.did_send {
+            return Err(SendError(value));
+        }
+        if let Some(waker) = self.emplace(value).ok().flatten() {
+            self.inner.wake_recv(&waker);
+        }
+        if self.inner.is_closed() {
+            // Only a Receiver which never took the value closes the
+            // channel, so nothing else can take it now.
+            if let Some(value) = self.inner.take_back() {
+                return Err(SendError(value));
+            }
+        }
+        Ok(())
+    }
+
+    /// Sends a message on the channel without waking the Receiver,
+    /// returning its waker (if it is waiting) for the caller to wake.
+    /// Fails if the Receiver is dropped.
+    ///
+    /// This is useful where waking may fail or must happen later, for
+    /// example while an executor is shutting down. The Receiver will
+    /// not notice the message until it is woken or polled again.
+    pub fn send_deferred(&mut self, value: T) -> Result<Option<Waker>, Closed> {
+        let waker = self.emplace(value)?;
+        self.check_closed().map(|()| waker)
+    }
+
+    /// Stores the value and takes the receiver's waker.
+    fn emplace(&mut self, value: T) -> Result<Option<Waker>, Closed> {
+        if self.did_send {
+            return Err(Closed());
+        }
+        self.did_send = true;
+        self.inner.emplace_value(value);
+
+        let mut recv_lock = self.inner.lock_recv();
+        let waker = recv_lock.take();
+        drop(recv_lock);
+        Ok(waker)
+    }
+
+    fn check_closed(&self) -> Result<(), Closed> {
+        if self.inner.is_closed() {
             Err(Closed())
         } else {
-            self.did_send = true;
+            Ok(())
+        }
+    }
+}
 
-            let inner = &mut self.inner;
-            inner.emplace_value(value);
+/// A future waiting for the Receiver to be waiting. Created by
+/// [`Sender::wait`].
+///
+/// Dropping it drops the Sender, closing the channel.
+#[derive(Debug)]
+pub struct Wait<T> {
+    sender: Option<Sender<T>>,
+}
 
-            // Attempt to wake up a receiver
-            let mut recv_lock = inner.lock_recv();
-            if let Some(waker) = recv_lock.take() {
-                waker.wake();
-            }
+impl<T> Wait<T> {
+    /// Stops waiting and returns the Sender, removing our waker so the
+    /// Receiver won't wake us. Returns `None` if the wait has already
+    /// completed.
+    pub fn cancel(mut self) -> Option<Sender<T>> {
+        let sender = self.sender.take()?;
+        sender.stop_waiting();
+        Some(sender)
+    }
+}
 
-            if inner.is_closed() {
-                Err(Closed())
-            } else {
-                Ok(())
+impl<T> Future for Wait<T> {
+    type Output = Result<Sender<T>, Closed>;
+    fn poll(self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Result<Sender<T>, Closed>> {
+        let this = Pin::into_inner(self);
+        #[allow(unused_mut)]
+        let mut sender = this.sender.take().expect("Wait polled after completion");
+        #[cfg(feature = "tracing")]
+        let _span = tracing::trace_span!(
+            "wait",
+            channel = sender.inner.id(),
+            label = sender.inner.label()
+        )
+        .entered();
+        #[cfg(feature = "diagnostics")]
+        {
+            sender.polls += 1;
+        }
+
+        // Attempt lock free check
+        if sender.is_closed() {
+            sender.stop_waiting();
+            return Poll::Ready(Err(Closed()));
+        }
+
+        let recv_lock = sender.inner.lock_recv();
+        if recv_lock.get().is_some() {
+            drop(recv_lock);
+
+            // A receiver is waiting for us
+            sender.stop_waiting();
+            return Poll::Ready(Ok(sender));
+        }
+
+        // Keep the receiver locked while we set a waker
+        let mut send_lock = sender.inner.lock_send();
+        let old_waker = send_lock.replace(ctx.waker().clone());
+        sender.inner.set_send_waiting(true);
+
+        // Drop both locks, we have a waker registered now
+        drop(send_lock);
+        drop(recv_lock);
+        drop(old_waker);
+
+        this.sender = Some(sender);
+        Poll::Pending
+    }
+}
+
+impl<T> Drop for Wait<T> {
+    fn drop(&mut self) {
+        if let Some(sender) = self.sender.take() {
+            sender.stop_waiting();
+        }
+    }
+}
+
+/// A future waiting for the Receiver to go away. Created by
+/// [`Sender::closed`].
+///
+/// Dropping it removes its waker from the channel.
+#[derive(Debug)]
+pub struct WaitClosed<'a, T> {
+    sender: &'a mut Sender<T>,
+}
+
+impl<T> Future for WaitClosed<'_, T> {
+    type Output = ();
+    fn poll(self: Pin<&mut Self>, ctx: &mut Context) -> Poll<()> {
+        Pin::into_inner(self).sender.poll_closed(ctx)
+    }
+}
+
+impl<T> Drop for WaitClosed<'_, T> {
+    fn drop(&mut self) {
+        self.sender.clear_send_waker();
+    }
+}
+
+/// Sends a clone of `value` on each of the Senders, returning the result
+/// for each in order. Values are only cloned for Senders which can still
+/// send, and no Receiver is woken until every value is in place.
+pub fn send_to_all<'a, T: Clone + 'a>(
+    senders: impl IntoIterator<Item = &'a mut Sender<T>>,
+    value: T,
+) -> Vec<Result<(), Closed>> {
+    let mut wakes = Vec::new();
+    let results = senders
+        .into_iter()
+        .map(|sender| {
+            if !sender.can_send() {
+                return Err(Closed());
+            }
+            let waker = sender.emplace(value.clone())?;
+            let sender: &'a Sender<T> = sender;
+            if let Some(waker) = waker {
+                wakes.push((sender, waker));
             }
+            sender.check_closed()
+        })
+        .collect();
+    for (sender, waker) in wakes {
+        sender.inner.wake_recv(&waker);
+    }
+    results
+}
+
+/// Forwards the message from `receiver` to `sender`, finishing once it is
+/// delivered or either channel closes.
+///
+/// Closure propagates both ways: if `receiver`'s Sender drops without
+/// sending, `sender` is dropped, closing its channel; if `sender`'s
+/// Receiver drops first, `receiver` is dropped, closing its channel.
+pub async fn pipe<T>(mut receiver: Receiver<T>, mut sender: Sender<T>) -> Result<(), Closed> {
+    let value = poll_fn(|ctx| {
+        if let Poll::Ready(result) = Pin::new(&mut receiver).poll(ctx) {
+            return Poll::Ready(result);
+        }
+        match sender.poll_closed(ctx) {
+            Poll::Ready(()) => Poll::Ready(Err(Closed())),
+            Poll::Pending => Poll::Pending,
         }
+    })
+    .await;
+    sender.send(value?)
+}
+
+/// A single-use completion object. Created by [`Sender::into_completer`].
+///
+/// Dropping it without completing closes the channel.
+#[derive(Debug)]
+pub struct Completer<T>(Sender<T>);
+
+impl<T> Completer<T> {
+    /// Completes the channel with a value. Fails if the Receiver is
+    /// dropped.
+    pub fn complete(mut self, value: T) -> Result<(), Closed> {
+        self.0.send(value)
+    }
+
+    /// true if the channel is closed.
+    ///
+    /// See [`Sender::is_closed`].
+    pub fn is_closed(&self) -> bool {
+        self.0.is_closed()
+    }
+
+    /// Converts this completer into a boxed closure, for APIs which
+    /// take a callback. Closure of the channel is ignored.
+    pub fn into_callback(self) -> Box<dyn FnOnce(T) + Send>
+    where
+        T: Send + 'static,
+    {
+        Box::new(move |value| {
+            let _ = self.complete(value);
+        })
     }
 }
 
 impl<T> Drop for Sender<T> {
     #[inline(always)]
     fn drop(&mut self) {
+        // Nothing is left to wake for a waker registered by a future
+        // that held us, such as `send_at`.
+        self.clear_send_waker();
         if !self.did_send {
             // Mark as closed
             self.inner.mark_closed();
 
             // Attempt to wake up a receiver
             let mut recv_lock = self.inner.lock_recv();
-            if let Some(waker) = recv_lock.take() {
-                waker.wake();
+            let waker = recv_lock.take();
+            drop(recv_lock);
+            if let Some(waker) = waker {
+                self.inner.wake_recv(&waker);
             }
         }
     }
 }
+
+impl<T> core::fmt::Debug for Sender<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let state = self.inner.flags();
+        f.debug_struct("Sender")
+            .field("state", &state.describe())
+            .field("receiver_waiting", &state.contains(flags::RECV_PRESENT))
+            .field("did_send", &self.did_send)
+            .finish()
+    }
+}
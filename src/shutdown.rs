@@ -0,0 +1,48 @@
+//! A shutdown signal that any number of tasks can wait for.
+
+use crate::broadcast::{self, broadcast};
+use core::future::Future;
+
+/// Create a new shutdown signal.
+///
+/// Clone the [`Listener`] to hand it to more tasks.
+pub fn shutdown() -> (Trigger, Listener) {
+    let (sender, receiver) = broadcast();
+    (Trigger(sender), Listener(receiver))
+}
+
+/// Fires the shutdown signal. Dropping it fires the signal too.
+#[derive(Debug)]
+pub struct Trigger(broadcast::Sender<()>);
+
+impl Trigger {
+    /// Fires the signal, waking all listeners. Calling this more than
+    /// once has no further effect.
+    pub fn trigger(&mut self) {
+        let _ = self.0.send(());
+    }
+
+    /// true if every listener has dropped.
+    pub fn is_closed(&self) -> bool {
+        self.0.is_closed()
+    }
+}
+
+/// Waits for the shutdown signal.
+#[derive(Clone, Debug)]
+pub struct Listener(broadcast::Receiver<()>);
+
+impl Listener {
+    /// true if the signal has fired.
+    pub fn is_triggered(&self) -> bool {
+        self.0.is_finished()
+    }
+
+    /// Waits until the signal fires.
+    pub fn wait(&self) -> impl Future<Output = ()> + 'static {
+        let receiver = self.0.clone();
+        async move {
+            let _ = receiver.await;
+        }
+    }
+}
@@ -0,0 +1,56 @@
+//! Hooks for deterministic simulation testing.
+//!
+//! With the `sim` feature, the points where a channel spins waiting
+//! for a lock or wakes the other side are routed through user
+//! supplied functions, so a simulator can decide when they happen.
+
+use core::sync::atomic::{AtomicPtr, Ordering};
+use core::task::Waker;
+
+/// Functions called at the channel's internal yield points.
+#[derive(Clone, Copy, Debug)]
+pub struct Hooks {
+    /// Called on each iteration while waiting for a lock held by the
+    /// other side.
+    pub spin: fn(),
+    /// Called to deliver a wake to the other side.
+    pub wake: fn(&Waker),
+}
+
+impl Hooks {
+    /// The hooks used when none are installed.
+    pub const DEFAULT: Hooks = Hooks {
+        spin: core::hint::spin_loop,
+        wake: Waker::wake_by_ref,
+    };
+}
+
+static HOOKS: AtomicPtr<Hooks> = AtomicPtr::new(core::ptr::null_mut());
+
+/// Installs the hooks for all channels.
+pub fn set_hooks(hooks: &'static Hooks) {
+    HOOKS.store(hooks as *const Hooks as *mut Hooks, Ordering::Release);
+}
+
+/// Restores the default hooks.
+pub fn reset_hooks() {
+    HOOKS.store(core::ptr::null_mut(), Ordering::Release);
+}
+
+fn hooks() -> &'static Hooks {
+    let hooks = HOOKS.load(Ordering::Acquire);
+    if hooks.is_null() {
+        &Hooks::DEFAULT
+    } else {
+        // SAFETY: Only ever set from a `&'static Hooks`.
+        unsafe { &*hooks }
+    }
+}
+
+pub(crate) fn spin() {
+    (hooks().spin)()
+}
+
+pub(crate) fn wake(waker: &Waker) {
+    (hooks().wake)(waker)
+}
@@ -0,0 +1,272 @@
+//! Deterministic helpers for testing code that uses these channels.
+
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+use alloc::task::Wake;
+use core::future::Future;
+use core::pin::Pin;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use core::task::{Context, Poll, Waker};
+
+struct Counter(AtomicUsize);
+
+impl Wake for Counter {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.0.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+/// A waker which counts how many times it has been woken.
+#[derive(Clone)]
+pub struct CountingWaker(Arc<Counter>);
+
+impl CountingWaker {
+    /// Creates a new waker which has not been woken.
+    pub fn new() -> Self {
+        CountingWaker(Arc::new(Counter(AtomicUsize::new(0))))
+    }
+
+    /// Returns a `Waker` which increments this counter.
+    pub fn waker(&self) -> Waker {
+        Waker::from(self.0.clone())
+    }
+
+    /// The number of times any of our `Waker`s have been woken.
+    pub fn count(&self) -> usize {
+        self.0 .0.load(Ordering::SeqCst)
+    }
+}
+
+impl Default for CountingWaker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl core::fmt::Debug for CountingWaker {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_tuple("CountingWaker").field(&self.count()).finish()
+    }
+}
+
+/// Drives a future one poll at a time, counting polls and wakes.
+pub struct Stepper<F> {
+    future: Pin<Box<F>>,
+    waker: CountingWaker,
+    polls: usize,
+}
+
+impl<F: Future> Stepper<F> {
+    /// Wraps a future without polling it.
+    pub fn new(future: F) -> Self {
+        Stepper {
+            future: Box::pin(future),
+            waker: CountingWaker::new(),
+            polls: 0,
+        }
+    }
+
+    /// Polls the future once.
+    ///
+    /// NOTE: Like any future, it must not be polled again after it has
+    /// returned `Poll::Ready`.
+    pub fn step(&mut self) -> Poll<F::Output> {
+        self.polls += 1;
+        let waker = self.waker.waker();
+        self.future.as_mut().poll(&mut Context::from_waker(&waker))
+    }
+
+    /// The number of times the future has been polled.
+    pub fn polls(&self) -> usize {
+        self.polls
+    }
+
+    /// The number of times the future's waker has been woken.
+    pub fn wakes(&self) -> usize {
+        self.waker.count()
+    }
+
+    /// Asserts the future's waker has been woken exactly `expected` times.
+    #[track_caller]
+    pub fn assert_wakes(&self, expected: usize) {
+        assert_eq!(expected, self.wakes(), "unexpected number of wakes");
+    }
+}
+
+impl<F> core::fmt::Debug for Stepper<F> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Stepper")
+            .field("waker", &self.waker)
+            .field("polls", &self.polls)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A scripted stand-in for a [`Receiver`](crate::Receiver), for testing
+/// code that handles receiving without a real Sender.
+///
+/// It returns `Poll::Pending` a set number of times, waking itself each
+/// time, then completes with its scripted result.
+pub struct MockReceiver<T> {
+    result: Option<Result<T, crate::Closed>>,
+    pending: usize,
+}
+
+impl<T> MockReceiver<T> {
+    /// A receiver which receives `value`.
+    pub fn value(value: T) -> Self {
+        MockReceiver {
+            result: Some(Ok(value)),
+            pending: 0,
+        }
+    }
+
+    /// A receiver whose Sender dropped without sending.
+    pub fn closed() -> Self {
+        MockReceiver {
+            result: Some(Err(crate::Closed())),
+            pending: 0,
+        }
+    }
+
+    /// Returns `Poll::Pending` `polls` times before completing.
+    pub fn after_pending(mut self, polls: usize) -> Self {
+        self.pending = polls;
+        self
+    }
+
+    /// true if the scripted result is that the channel closed.
+    pub fn is_closed(&self) -> bool {
+        matches!(self.result, Some(Err(_)) | None)
+    }
+
+    /// Attempts to receive, counting as a poll. Returns `None` while the
+    /// receiver is scripted to be pending.
+    pub fn try_recv(&mut self) -> Option<Result<T, crate::Closed>> {
+        if self.pending > 0 {
+            self.pending -= 1;
+            return None;
+        }
+        Some(self.result.take().unwrap_or(Err(crate::Closed())))
+    }
+}
+
+impl<T> Unpin for MockReceiver<T> {}
+
+impl<T> Future for MockReceiver<T> {
+    type Output = Result<T, crate::Closed>;
+    fn poll(self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Self::Output> {
+        match Pin::into_inner(self).try_recv() {
+            Some(result) => Poll::Ready(result),
+            None => {
+                ctx.waker().wake_by_ref();
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl<T> core::fmt::Debug for MockReceiver<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("MockReceiver")
+            .field("pending", &self.pending)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A scripted stand-in for a [`Sender`](crate::Sender), for testing
+/// code that handles sending without a real Receiver.
+///
+/// It records every value it accepts.
+pub struct MockSender<T> {
+    sent: alloc::vec::Vec<T>,
+    receiver_dropped: bool,
+    pending: usize,
+}
+
+impl<T> MockSender<T> {
+    /// A sender whose Receiver accepts one value.
+    pub fn new() -> Self {
+        MockSender {
+            sent: alloc::vec::Vec::new(),
+            receiver_dropped: false,
+            pending: 0,
+        }
+    }
+
+    /// A sender whose Receiver has already dropped.
+    pub fn closed() -> Self {
+        MockSender {
+            sent: alloc::vec::Vec::new(),
+            receiver_dropped: true,
+            pending: 0,
+        }
+    }
+
+    /// Makes [`MockSender::wait`] return `Poll::Pending` `polls` times
+    /// before completing.
+    pub fn after_pending(mut self, polls: usize) -> Self {
+        self.pending = polls;
+        self
+    }
+
+    /// true if the Receiver is scripted to have dropped.
+    ///
+    /// As with [`Sender::is_closed`](crate::Sender::is_closed), sending
+    /// a value doesn't close the channel.
+    pub fn is_closed(&self) -> bool {
+        self.receiver_dropped
+    }
+
+    /// Records the value, or fails if the Receiver is scripted to have
+    /// dropped or the value has already been sent.
+    pub fn send(&mut self, value: T) -> Result<(), crate::Closed> {
+        if self.receiver_dropped || !self.sent.is_empty() {
+            return Err(crate::Closed());
+        }
+        self.sent.push(value);
+        Ok(())
+    }
+
+    /// Waits for the scripted number of polls, then returns the sender,
+    /// or fails if the Receiver is scripted to have dropped.
+    pub fn wait(self) -> impl Future<Output = Result<Self, crate::Closed>> {
+        let mut sender = Some(self);
+        core::future::poll_fn(move |ctx| {
+            let this = sender.as_mut().expect("polled after completion");
+            if this.pending > 0 {
+                this.pending -= 1;
+                ctx.waker().wake_by_ref();
+                return Poll::Pending;
+            }
+            if this.is_closed() {
+                return Poll::Ready(Err(crate::Closed()));
+            }
+            Poll::Ready(Ok(sender.take().unwrap()))
+        })
+    }
+
+    /// The values sent so far.
+    pub fn sent(&self) -> &[T] {
+        &self.sent
+    }
+}
+
+impl<T> Default for MockSender<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> core::fmt::Debug for MockSender<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("MockSender")
+            .field("sent", &self.sent.len())
+            .field("closed", &self.is_closed())
+            .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,77 @@
+//! Timeouts driven by a user supplied clock, for use without `std`.
+
+use core::fmt;
+use core::task::{Context, Poll, Waker};
+use core::{future::Future, pin::Pin};
+
+/// A monotonic clock which can wake tasks at a deadline, typically
+/// backed by a hardware timer interrupt.
+pub trait Timer {
+    /// A point in time.
+    type Instant: Ord + Copy + Unpin;
+
+    /// The current time.
+    fn now(&self) -> Self::Instant;
+
+    /// Arranges for `waker` to be woken at or after `deadline`.
+    fn register_wake_at(&self, deadline: Self::Instant, waker: &Waker);
+}
+
+/// An error that signifies the deadline passed.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct TimedOut();
+
+impl fmt::Display for TimedOut {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("deadline passed")
+    }
+}
+
+impl core::error::Error for TimedOut {}
+
+/// Limits a future to complete before a deadline.
+///
+/// Pass a `&mut` to a [`Receiver`](crate::Receiver) to keep it when the
+/// deadline passes.
+pub fn timeout_at<F: Future + Unpin, T: Timer>(
+    future: F,
+    timer: &T,
+    deadline: T::Instant,
+) -> TimeoutAt<'_, F, T> {
+    TimeoutAt {
+        future,
+        timer,
+        deadline,
+    }
+}
+
+/// A future limited to complete before a deadline. Created by
+/// [`timeout_at`].
+pub struct TimeoutAt<'a, F, T: Timer> {
+    future: F,
+    timer: &'a T,
+    deadline: T::Instant,
+}
+
+impl<F: Future + Unpin, T: Timer> Future for TimeoutAt<'_, F, T> {
+    type Output = Result<F::Output, TimedOut>;
+    fn poll(self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Self::Output> {
+        let this = Pin::into_inner(self);
+        if let Poll::Ready(v) = Pin::new(&mut this.future).poll(ctx) {
+            return Poll::Ready(Ok(v));
+        }
+        if this.timer.now() >= this.deadline {
+            return Poll::Ready(Err(TimedOut()));
+        }
+        this.timer.register_wake_at(this.deadline, ctx.waker());
+        Poll::Pending
+    }
+}
+
+impl<F: fmt::Debug, T: Timer> fmt::Debug for TimeoutAt<'_, F, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TimeoutAt")
+            .field("future", &self.future)
+            .finish_non_exhaustive()
+    }
+}
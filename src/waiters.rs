@@ -0,0 +1,116 @@
+//! Waker lists for primitives which many tasks can wait on at once.
+//!
+//! Each waiter registers under its own id, so it can update its waker
+//! in place when polled again and remove it when dropped, rather than
+//! leaving it behind until the next wake.
+
+use crate::mutex::Mutex;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use core::task::Waker;
+
+/// Hands out the ids waiters register under.
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// Returns an id not used by any other waiter.
+pub(crate) fn next_id() -> usize {
+    NEXT_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+/// A list of wakers keyed by waiter id.
+///
+/// Like [`Mutex`], this stores its locking state in an externally
+/// supplied atomic usize.
+#[derive(Debug)]
+pub(crate) struct Waiters<const PRESENT_BIT: usize, const LOCKED_BIT: usize> {
+    list: Mutex<Vec<(usize, Waker)>, PRESENT_BIT, LOCKED_BIT>,
+}
+
+impl<const PRESENT_BIT: usize, const LOCKED_BIT: usize> Waiters<PRESENT_BIT, LOCKED_BIT> {
+    pub(crate) const fn new() -> Self {
+        Waiters { list: Mutex::new() }
+    }
+
+    /// Adds or updates the waker registered under `id`.
+    ///
+    /// # Safety
+    ///
+    /// As for [`Mutex::lock`].
+    pub(crate) unsafe fn register(&self, state: &AtomicUsize, id: usize, waker: &Waker) {
+        let mut lock = self.list.lock(state);
+        let mut list = lock.take().unwrap_or_default();
+        let old_waker = match list.iter_mut().find(|(i, _)| *i == id) {
+            Some((_, w)) if w.will_wake(waker) => None,
+            Some((_, w)) => Some(core::mem::replace(w, waker.clone())),
+            None => {
+                list.push((id, waker.clone()));
+                None
+            }
+        };
+        lock.replace(list);
+        drop(lock);
+        drop(old_waker);
+    }
+
+    /// Removes the waker registered under `id`. Returns false if there
+    /// was none, for example because it has already been woken.
+    ///
+    /// # Safety
+    ///
+    /// As for [`Mutex::lock`].
+    pub(crate) unsafe fn deregister(&self, state: &AtomicUsize, id: usize) -> bool {
+        if state.load(Ordering::Acquire) & (1 << PRESENT_BIT) == 0 {
+            return false;
+        }
+        let mut lock = self.list.lock(state);
+        let mut list = lock.take().unwrap_or_default();
+        let old_waker = list
+            .iter()
+            .position(|(i, _)| *i == id)
+            .map(|index| list.remove(index).1);
+        if !list.is_empty() {
+            lock.replace(list);
+        }
+        drop(lock);
+        old_waker.is_some()
+    }
+
+    /// Removes and returns the longest-registered waker.
+    ///
+    /// # Safety
+    ///
+    /// As for [`Mutex::lock`].
+    pub(crate) unsafe fn take_first(&self, state: &AtomicUsize) -> Option<Waker> {
+        if state.load(Ordering::Acquire) & (1 << PRESENT_BIT) == 0 {
+            return None;
+        }
+        let mut lock = self.list.lock(state);
+        let mut list = lock.take()?;
+        let (_, waker) = list.remove(0);
+        if !list.is_empty() {
+            lock.replace(list);
+        }
+        drop(lock);
+        Some(waker)
+    }
+
+    /// Removes and returns every waker.
+    ///
+    /// # Safety
+    ///
+    /// As for [`Mutex::lock`].
+    pub(crate) unsafe fn take_all(&self, state: &AtomicUsize) -> Vec<Waker> {
+        if state.load(Ordering::Acquire) & (1 << PRESENT_BIT) == 0 {
+            return Vec::new();
+        }
+        let mut lock = self.list.lock(state);
+        let list = lock.take();
+        drop(lock);
+        list.into_iter().flatten().map(|(_, waker)| waker).collect()
+    }
+
+    /// Needs to be called in order to drop the list without leaking it.
+    pub(crate) fn drop(&mut self, state: &AtomicUsize) {
+        self.list.drop(state);
+    }
+}
@@ -0,0 +1,203 @@
+//! Property tests checking random interleavings of the public API
+//! against the reference model of the channel protocol.
+use async_oneshot::model::{Model, Op, Outcome};
+use async_oneshot::*;
+use core::future::Future;
+use core::pin::Pin;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use core::task::{Context, Poll, Waker};
+use proptest::prelude::*;
+use std::sync::{Arc, Barrier};
+use std::thread;
+use waker_fn::waker_fn;
+
+type WaitFuture = Pin<Box<dyn Future<Output = Result<Sender<u8>, Closed>> + Send>>;
+
+fn op() -> impl Strategy<Value = Op> {
+    prop_oneof![sender_op(), receiver_op()]
+}
+
+fn sender_op() -> impl Strategy<Value = Op> {
+    prop_oneof![
+        any::<u8>().prop_map(Op::Send),
+        Just(Op::Wait),
+        Just(Op::DropSender),
+    ]
+}
+
+fn receiver_op() -> impl Strategy<Value = Op> {
+    prop_oneof![Just(Op::TryRecv), Just(Op::Poll), Just(Op::DropReceiver)]
+}
+
+/// The real channel, driven by the same operations as the model.
+struct Real {
+    sender: Option<Sender<u8>>,
+    wait: Option<WaitFuture>,
+    receiver: Option<Receiver<u8>>,
+    recv_wakes: Arc<AtomicUsize>,
+    send_wakes: Arc<AtomicUsize>,
+}
+
+fn counting_waker(count: &Arc<AtomicUsize>) -> Waker {
+    let count = count.clone();
+    waker_fn(move || {
+        count.fetch_add(1, Ordering::SeqCst);
+    })
+}
+
+impl Real {
+    fn new() -> Self {
+        let (s, r) = oneshot();
+        Real {
+            sender: Some(s),
+            wait: None,
+            receiver: Some(r),
+            recv_wakes: Arc::new(AtomicUsize::new(0)),
+            send_wakes: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    /// A sender half and a receiver half of one channel, each driven
+    /// with only its own side's operations.
+    fn halves() -> (Self, Self) {
+        let mut sender = Real::new();
+        let receiver = Real {
+            sender: None,
+            receiver: sender.receiver.take(),
+            ..Real::new()
+        };
+        (sender, receiver)
+    }
+
+    fn poll_wait(&mut self) -> Outcome {
+        let waker = counting_waker(&self.send_wakes);
+        let mut ctx = Context::from_waker(&waker);
+        let mut wait = self.wait.take().unwrap();
+        match wait.as_mut().poll(&mut ctx) {
+            Poll::Ready(Ok(s)) => {
+                self.sender = Some(s);
+                Outcome::Waited(Poll::Ready(Ok(())))
+            }
+            Poll::Ready(Err(e)) => Outcome::Waited(Poll::Ready(Err(e))),
+            Poll::Pending => {
+                self.wait = Some(wait);
+                Outcome::Waited(Poll::Pending)
+            }
+        }
+    }
+
+    fn apply(&mut self, op: Op) -> Outcome {
+        match op {
+            Op::Send(v) => match self.sender.as_mut() {
+                Some(s) => Outcome::Sent(s.send(v)),
+                None => Outcome::Skipped,
+            },
+            Op::Wait => {
+                if let Some(s) = self.sender.take() {
+                    self.wait = Some(Box::pin(s.wait()));
+                }
+                if self.wait.is_some() {
+                    self.poll_wait()
+                } else {
+                    Outcome::Skipped
+                }
+            }
+            Op::DropSender => {
+                if self.sender.take().is_some() || self.wait.take().is_some() {
+                    Outcome::Dropped
+                } else {
+                    Outcome::Skipped
+                }
+            }
+            Op::TryRecv => match self.receiver.take() {
+                Some(r) => Outcome::TryReceived(match r.try_recv() {
+                    Ok(v) => Ok(v),
+                    Err(TryRecvError::Empty(r)) => {
+                        self.receiver = Some(r);
+                        Err(None)
+                    }
+                    Err(TryRecvError::Closed) => Err(Some(Closed())),
+                }),
+                None => Outcome::Skipped,
+            },
+            Op::Poll => match self.receiver.as_mut() {
+                Some(r) => {
+                    let waker = counting_waker(&self.recv_wakes);
+                    let mut ctx = Context::from_waker(&waker);
+                    let poll = Pin::new(r).poll(&mut ctx);
+                    if poll.is_ready() {
+                        self.receiver = None;
+                    }
+                    Outcome::Received(poll)
+                }
+                None => Outcome::Skipped,
+            },
+            Op::DropReceiver => match self.receiver.take() {
+                Some(_) => Outcome::Dropped,
+                None => Outcome::Skipped,
+            },
+        }
+    }
+}
+
+proptest! {
+    #[test]
+    fn matches_model(ops in proptest::collection::vec(op(), 0..32)) {
+        let mut model = Model::new();
+        let mut real = Real::new();
+        for op in ops {
+            prop_assert_eq!(model.apply(op), real.apply(op), "{:?}", op);
+            prop_assert_eq!(model.recv_wakes(), real.recv_wakes.load(Ordering::SeqCst));
+            prop_assert_eq!(model.send_wakes(), real.send_wakes.load(Ordering::SeqCst));
+        }
+    }
+
+    #[test]
+    fn two_threads(
+        send_ops in proptest::collection::vec(sender_op(), 0..12),
+        recv_ops in proptest::collection::vec(receiver_op(), 0..12),
+    ) {
+        let (mut sender, mut receiver) = Real::halves();
+        let start = Arc::new(Barrier::new(2));
+        let recv_start = start.clone();
+        let receiving = thread::spawn(move || {
+            recv_start.wait();
+            let received: Vec<_> = recv_ops
+                .into_iter()
+                .map(|op| (op, step(&mut receiver, op)))
+                .collect();
+            // Dropping the handles here would be an operation the model
+            // doesn't know about.
+            (received, receiver)
+        });
+        start.wait();
+        let sent: Vec<_> = send_ops.into_iter().map(|op| (op, step(&mut sender, op))).collect();
+        let (received, _receiver) = receiving.join().unwrap();
+        prop_assert!(
+            explains(&Model::new(), &sent, &received),
+            "no interleaving of {:?} and {:?} matches the model",
+            sent,
+            received
+        );
+    }
+}
+
+/// Applies an operation, then gives the other thread a chance to run.
+fn step(real: &mut Real, op: Op) -> Outcome {
+    let outcome = real.apply(op);
+    thread::yield_now();
+    outcome
+}
+
+/// true if some interleaving of the two threads' operations, applied to
+/// `model` in turn, reports the outcomes they saw.
+fn explains(model: &Model, sent: &[(Op, Outcome)], received: &[(Op, Outcome)]) -> bool {
+    let apply = |ops: &[(Op, Outcome)]| {
+        let (op, outcome) = ops.first()?;
+        let mut model = model.clone();
+        (model.apply(*op) == *outcome).then_some(model)
+    };
+    (sent.is_empty() && received.is_empty())
+        || apply(sent).is_some_and(|model| explains(&model, &sent[1..], received))
+        || apply(received).is_some_and(|model| explains(&model, sent, &received[1..]))
+}
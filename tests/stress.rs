@@ -0,0 +1,76 @@
+//! Cross-thread races the single-threaded suites can't hit. Iteration
+//! counts are cut down under Miri, which is orders of magnitude slower.
+use async_oneshot::*;
+use futures::executor::block_on;
+use std::thread;
+use std::time::Duration;
+
+#[cfg(miri)]
+const ITERATIONS: u64 = 16;
+#[cfg(not(miri))]
+const ITERATIONS: u64 = 2_000;
+
+/// Sleeps or yields for a pseudo-random moment, to shake up interleavings.
+fn jitter(seed: &mut u64) {
+    *seed ^= *seed << 13;
+    *seed ^= *seed >> 7;
+    *seed ^= *seed << 17;
+    match *seed % 4 {
+        0 => {}
+        1 => thread::yield_now(),
+        _ => thread::sleep(Duration::from_micros(*seed % 20)),
+    }
+}
+
+#[test]
+fn drop_while_sending() {
+    for i in 0..ITERATIONS {
+        let (mut s, r) = oneshot::<Box<u64>>();
+        let receiver = thread::spawn(move || {
+            let mut seed = i * 2 + 1;
+            jitter(&mut seed);
+            drop(r);
+        });
+        let mut seed = i * 2 + 2;
+        jitter(&mut seed);
+        // Either outcome is fine; the value must not leak or double-drop.
+        let _ = s.send(Box::new(i));
+        receiver.join().unwrap();
+    }
+}
+
+#[test]
+fn send_while_receiving() {
+    for i in 0..ITERATIONS {
+        let (mut s, r) = oneshot::<Box<u64>>();
+        let receiver = thread::spawn(move || block_on(r));
+        let mut seed = i + 1;
+        jitter(&mut seed);
+        s.send(Box::new(i)).unwrap();
+        assert_eq!(Ok(Box::new(i)), receiver.join().unwrap());
+    }
+}
+
+#[test]
+fn close_while_waiting() {
+    for i in 0..ITERATIONS {
+        let (s, r) = oneshot::<u64>();
+        let sender = thread::spawn(move || block_on(s.wait()).map(drop));
+        let mut seed = i + 1;
+        jitter(&mut seed);
+        drop(r);
+        assert_eq!(Err(Closed()), sender.join().unwrap());
+    }
+}
+
+#[test]
+fn drop_sender_while_receiving() {
+    for i in 0..ITERATIONS {
+        let (s, r) = oneshot::<u64>();
+        let receiver = thread::spawn(move || block_on(r));
+        let mut seed = i + 1;
+        jitter(&mut seed);
+        drop(s);
+        assert_eq!(Err(Closed()), receiver.join().unwrap());
+    }
+}
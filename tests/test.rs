@@ -112,3 +112,1301 @@ fn close_wait() {
     r.close();
     assert_eq!(Closed(), block_on(s.wait()).unwrap_err());
 }
+
+#[cfg(feature = "diagnostics")]
+#[test]
+fn diagnostics() {
+    let (s, mut r) = oneshot::<i32>();
+    let waker = waker_fn(|| ());
+    let mut ctx = Context::from_waker(&waker);
+    assert_eq!(Receiver::poll(Pin::new(&mut r), &mut ctx), Poll::Pending);
+    assert_eq!(Receiver::poll(Pin::new(&mut r), &mut ctx), Poll::Pending);
+    assert_eq!((2, 0), (r.polls(), r.wakes()));
+    let mut s = block_on(s.wait()).unwrap();
+    assert_eq!((1, 0), (s.polls(), s.wakes()));
+    s.send(42).unwrap();
+    assert_eq!((2, 1), (r.polls(), r.wakes()));
+    assert_eq!(Receiver::poll(Pin::new(&mut r), &mut ctx), Poll::Ready(Ok(42)));
+    assert_eq!(3, r.polls());
+}
+
+#[test]
+fn is_closed() {
+    let (s, r) = oneshot::<bool>();
+    assert!(!s.is_closed());
+    assert!(!r.is_closed());
+    s.close();
+    assert!(r.is_closed());
+
+    let (s, r) = oneshot::<bool>();
+    r.close();
+    assert!(s.is_closed());
+}
+
+#[test]
+fn map_recv() {
+    let (mut s, r) = oneshot::<i32>();
+    assert_eq!(
+        block_on(join(r.map(|v| v * 2), async { s.send(21).unwrap() })),
+        (Ok(42), ())
+    )
+}
+
+#[test]
+fn map_try_recv() {
+    let (mut s, r) = oneshot::<i32>();
+    let mut r = r.map(|v| v.to_string());
+    assert_eq!(None, r.try_recv());
+    s.send(42).unwrap();
+    assert_eq!(Some(Ok("42".to_string())), r.try_recv());
+    assert_eq!(Some(Err(Closed())), r.try_recv());
+}
+
+#[test]
+fn map_close() {
+    let (s, r) = oneshot::<i32>();
+    s.close();
+    assert_eq!(Err(Closed()), block_on(r.map(|v| v + 1)));
+}
+
+#[test]
+fn inspect() {
+    let (s, r) = oneshot::<i32>();
+    let mut sent = None;
+    let mut received = None;
+    let mut s = s.inspect(|v| sent = Some(*v));
+    let r = r.inspect(|v| received = Some(*v));
+    assert_eq!(
+        block_on(join(r, async { s.send(42).unwrap() })),
+        (Ok(42), ())
+    );
+    assert_eq!(Err(Closed()), s.send(43));
+    drop(s);
+    assert_eq!((Some(42), Some(42)), (sent, received));
+
+    // The adapters are Debug whatever the payload.
+    struct Opaque;
+    let (s, r) = oneshot::<Opaque>();
+    let mut inspected = 0;
+    let mut r = r.inspect(|_| inspected += 1);
+    assert_eq!(None, r.try_recv().map(|r| r.is_ok()));
+    assert!(format!("{:?}", r).starts_with("InspectReceiver"));
+    let mut s = s.inspect(|_| ());
+    assert!(format!("{:?}", s).starts_with("InspectSender"));
+    s.send(Opaque).unwrap();
+    assert!(matches!(r.try_recv(), Some(Ok(Opaque))));
+    drop(r);
+    assert_eq!(1, inspected);
+}
+
+#[derive(Debug, Eq, PartialEq)]
+enum FlattenError {
+    Closed,
+    Failed,
+}
+
+impl From<Closed> for FlattenError {
+    fn from(_: Closed) -> Self {
+        FlattenError::Closed
+    }
+}
+
+#[test]
+fn flatten() {
+    let (mut s, r) = oneshot::<Result<i32, FlattenError>>();
+    s.send(Ok(42)).unwrap();
+    assert_eq!(Ok(42), block_on(r.flatten()));
+
+    let (mut s, r) = oneshot::<Result<i32, FlattenError>>();
+    s.send(Err(FlattenError::Failed)).unwrap();
+    assert_eq!(Err(FlattenError::Failed), block_on(r.flatten()));
+
+    let (s, r) = oneshot::<Result<i32, FlattenError>>();
+    let mut r = r.flatten();
+    assert_eq!(None, r.try_recv());
+    s.close();
+    assert_eq!(Some(Err(FlattenError::Closed)), r.try_recv());
+}
+
+#[test]
+fn closed_display() {
+    assert_eq!("channel closed", Closed().to_string());
+    let (_s, r) = oneshot::<i32>();
+    let err = r.try_recv().unwrap_err();
+    assert_eq!("channel empty", err.to_string());
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn closed_io_error() {
+    let err = std::io::Error::from(Closed());
+    assert_eq!(std::io::ErrorKind::BrokenPipe, err.kind());
+}
+
+#[test]
+fn error_enum() {
+    let (mut s, r) = oneshot::<i32>();
+    drop(r);
+    assert_eq!(Err(Error::Closed), s.try_send(42).map_err(Error::from));
+    assert_eq!(Error::TimedOut, Error::from(timer::TimedOut()));
+    assert_eq!("channel closed", Error::from(Closed()).to_string());
+}
+
+#[cfg(feature = "futures-core")]
+#[test]
+fn fused() {
+    use futures::future::FusedFuture;
+    let (mut s, mut r) = oneshot::<i32>();
+    assert!(!r.is_terminated());
+    s.send(42).unwrap();
+    assert_eq!(Ok(42), block_on(&mut r));
+    assert!(r.is_terminated());
+
+    let (s, mut r) = oneshot::<i32>();
+    s.close();
+    assert_eq!(Err(Closed()), block_on(&mut r));
+    assert!(r.is_terminated());
+}
+
+#[test]
+fn wake_drops_receiver() {
+    // The waker drops the receiver while it is being woken, which must
+    // not happen while the sender still holds the receiver's lock.
+    let (mut s, r) = oneshot::<i32>();
+    let r = std::sync::Arc::new(std::sync::Mutex::new(Some(r)));
+    let r2 = r.clone();
+    let waker = waker_fn(move || drop(r2.lock().unwrap().take()));
+    let mut ctx = Context::from_waker(&waker);
+    let poll = Receiver::poll(Pin::new(r.lock().unwrap().as_mut().unwrap()), &mut ctx);
+    assert_eq!(Poll::Pending, poll);
+    // The receiver dropped without taking the value before send returned.
+    assert_eq!(Err(Closed()), s.send(42));
+    assert!(r.lock().unwrap().is_none());
+}
+
+#[test]
+fn update_waker_while_sending() {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+
+    fn flag_waker() -> (Arc<AtomicBool>, core::task::Waker) {
+        let flag = Arc::new(AtomicBool::new(false));
+        let woken = flag.clone();
+        (flag, waker_fn(move || woken.store(true, Ordering::SeqCst)))
+    }
+
+    for i in 0..200 {
+        let (mut s, mut r) = oneshot::<i32>();
+        let sender = std::thread::spawn(move || {
+            for _ in 0..i % 20 {
+                std::thread::yield_now();
+            }
+            s.send(42).unwrap();
+        });
+        loop {
+            // Replace the stored waker, then rely on only the newest one.
+            let (_, stale) = flag_waker();
+            let (flag, waker) = flag_waker();
+            if let Poll::Ready(v) = Receiver::poll(Pin::new(&mut r), &mut Context::from_waker(&stale)) {
+                assert_eq!(Ok(42), v);
+                break;
+            }
+            if let Poll::Ready(v) = Receiver::poll(Pin::new(&mut r), &mut Context::from_waker(&waker)) {
+                assert_eq!(Ok(42), v);
+                break;
+            }
+            let start = Instant::now();
+            while !flag.load(Ordering::SeqCst) {
+                assert!(start.elapsed() < Duration::from_secs(10), "lost wakeup");
+                std::thread::yield_now();
+            }
+        }
+        sender.join().unwrap();
+    }
+}
+
+#[cfg(feature = "parking")]
+#[test]
+fn blocking() {
+    let (s, r) = oneshot::<i32>();
+    let receiver = std::thread::spawn(move || r.recv_blocking());
+    let mut s = s.wait_blocking().unwrap();
+    s.send(42).unwrap();
+    assert_eq!(Ok(42), receiver.join().unwrap());
+
+    let (s, r) = oneshot::<i32>();
+    let receiver = std::thread::spawn(move || r.recv_blocking());
+    s.wait_blocking().unwrap().close();
+    assert_eq!(Err(Closed()), receiver.join().unwrap());
+
+    let (s, r) = oneshot::<i32>();
+    r.close();
+    assert_eq!(Closed(), s.wait_blocking().unwrap_err());
+
+    // Park straight away rather than spinning first.
+    let (s, r) = oneshot::<i32>();
+    let receiver = std::thread::spawn(move || block_on_spinning(r, 0));
+    let mut s = block_on_spinning(s.wait(), 0).unwrap();
+    s.send(42).unwrap();
+    assert_eq!(Ok(42), receiver.join().unwrap());
+}
+
+#[test]
+fn broadcast_send_recv() {
+    let (mut s, r) = broadcast::<i32>();
+    let r2 = r.clone();
+    let mut r3 = r.clone();
+    assert_eq!(None, r3.try_recv());
+    assert_eq!(
+        block_on(join(join(r, r2), async { s.send(42).unwrap() })),
+        ((Ok(42), Ok(42)), ())
+    );
+    assert_eq!(Some(Ok(42)), r3.try_recv());
+
+    // Once it has returned a result, a receiver doesn't clone the value again.
+    assert_eq!(Some(Err(Closed())), r3.try_recv());
+    assert_eq!(Err(Closed()), block_on(&mut r3));
+}
+
+#[test]
+fn broadcast_taken() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    let wakes = Arc::new(AtomicUsize::new(0));
+    let waker = {
+        let wakes = wakes.clone();
+        waker_fn(move || { wakes.fetch_add(1, Ordering::SeqCst); })
+    };
+    let mut ctx = Context::from_waker(&waker);
+    let (mut s, mut r) = broadcast::<i32>();
+    let r2 = r.clone();
+    s.send(42).unwrap();
+    let mut taken = s.taken();
+    assert!(Pin::new(&mut taken).poll(&mut ctx).is_pending());
+
+    // Completes once each receiver has either taken the value or dropped.
+    assert_eq!(Some(Ok(42)), r.try_recv());
+    assert!(Pin::new(&mut taken).poll(&mut ctx).is_pending());
+    assert_eq!(0, wakes.load(Ordering::SeqCst));
+    drop(r2);
+    assert_eq!(1, wakes.load(Ordering::SeqCst));
+    assert!(Pin::new(&mut taken).poll(&mut ctx).is_ready());
+    drop(r);
+    assert_eq!(1, wakes.load(Ordering::SeqCst));
+
+    // A dropped wait isn't woken.
+    let (mut s, r) = broadcast::<i32>();
+    assert!(Pin::new(&mut s.taken()).poll(&mut ctx).is_pending());
+    s.send(42).unwrap();
+    assert_eq!(Ok(42), block_on(r));
+    assert_eq!(1, wakes.load(Ordering::SeqCst));
+    block_on(s.taken());
+}
+
+#[test]
+fn broadcast_deregisters() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    let counted = |wakes: &Arc<AtomicUsize>| {
+        let wakes = wakes.clone();
+        waker_fn(move || { wakes.fetch_add(1, Ordering::SeqCst); })
+    };
+    let (stale, live, dropped) = (
+        Arc::new(AtomicUsize::new(0)),
+        Arc::new(AtomicUsize::new(0)),
+        Arc::new(AtomicUsize::new(0)),
+    );
+    let (mut s, mut r) = broadcast::<i32>();
+    let mut r2 = r.clone();
+    assert!(Pin::new(&mut r).poll(&mut Context::from_waker(&counted(&stale))).is_pending());
+    assert!(Pin::new(&mut r).poll(&mut Context::from_waker(&counted(&live))).is_pending());
+    assert!(Pin::new(&mut r2).poll(&mut Context::from_waker(&counted(&dropped))).is_pending());
+
+    // Repolling replaces the waker, and dropping removes it.
+    drop(r2);
+    s.send(42).unwrap();
+    assert_eq!((0, 1, 0), (stale.load(Ordering::SeqCst), live.load(Ordering::SeqCst), dropped.load(Ordering::SeqCst)));
+}
+
+#[test]
+fn broadcast_close() {
+    let (s, r) = broadcast::<i32>();
+    let r2 = r.clone();
+    assert_eq!(
+        block_on(join(join(r, r2), async { s.close() })),
+        ((Err(Closed()), Err(Closed())), ())
+    );
+
+    let (mut s, r) = broadcast::<i32>();
+    let r2 = r.clone();
+    r.close();
+    assert!(!s.is_closed());
+    r2.close();
+    assert!(s.is_closed());
+    assert_eq!(Err(Closed()), s.send(42));
+}
+
+#[test]
+fn shutdown_signal() {
+    let (mut t, l) = shutdown();
+    let l2 = l.clone();
+    assert!(!l.is_triggered());
+    assert_eq!(
+        block_on(join(join(l.wait(), l2.wait()), async { t.trigger() })),
+        (((), ()), ())
+    );
+    t.trigger();
+    assert!(l.is_triggered());
+    block_on(l.wait());
+
+    let (t, l) = shutdown();
+    drop(t);
+    assert!(l.is_triggered());
+}
+
+#[test]
+fn send_deferred() {
+    let (mut s, mut r) = oneshot::<i32>();
+    let woken = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+    let flag = woken.clone();
+    let waker = waker_fn(move || flag.store(true, std::sync::atomic::Ordering::SeqCst));
+    let mut ctx = Context::from_waker(&waker);
+    assert_eq!(Receiver::poll(Pin::new(&mut r), &mut ctx), Poll::Pending);
+    let deferred = s.send_deferred(42).unwrap().unwrap();
+    assert!(!woken.load(std::sync::atomic::Ordering::SeqCst));
+    deferred.wake();
+    assert!(woken.load(std::sync::atomic::Ordering::SeqCst));
+    assert_eq!(Receiver::poll(Pin::new(&mut r), &mut ctx), Poll::Ready(Ok(42)));
+
+    let (mut s, r) = oneshot::<i32>();
+    r.close();
+    assert_eq!(Closed(), s.send_deferred(42).unwrap_err());
+}
+
+#[cfg(feature = "test-util")]
+#[test]
+fn stepper() {
+    use async_oneshot::test_util::Stepper;
+    let (s, r) = oneshot::<i32>();
+    let mut recv = Stepper::new(r);
+    let mut wait = Stepper::new(s.wait());
+    assert!(wait.step().is_pending());
+    assert!(recv.step().is_pending());
+    wait.assert_wakes(1);
+    let mut s = match wait.step() {
+        Poll::Ready(s) => s.unwrap(),
+        Poll::Pending => panic!("receiver is waiting"),
+    };
+    recv.assert_wakes(0);
+    s.send(42).unwrap();
+    recv.assert_wakes(1);
+    assert_eq!(Poll::Ready(Ok(42)), recv.step());
+    assert_eq!(2, recv.polls());
+}
+
+#[cfg(feature = "test-util")]
+#[test]
+fn mock_handles() {
+    use async_oneshot::test_util::{MockReceiver, MockSender, Stepper};
+    let mut recv = Stepper::new(MockReceiver::value(42).after_pending(2));
+    assert!(recv.step().is_pending());
+    assert!(recv.step().is_pending());
+    assert_eq!(Poll::Ready(Ok(42)), recv.step());
+    recv.assert_wakes(2);
+    assert_eq!(Err(Closed()), block_on(MockReceiver::<i32>::closed()));
+
+    let mut wait = Stepper::new(MockSender::new().after_pending(1).wait());
+    assert!(wait.step().is_pending());
+    let mut s = match wait.step() {
+        Poll::Ready(s) => s.unwrap(),
+        Poll::Pending => panic!("sender should be ready"),
+    };
+    s.send(42).unwrap();
+    // Like a real Sender, sending doesn't make it report closed.
+    assert!(!s.is_closed());
+    assert_eq!(Err(Closed()), s.send(43));
+    assert_eq!(&[42], s.sent());
+    assert!(MockSender::<i32>::closed().is_closed());
+    assert!(block_on(MockSender::<i32>::closed().wait()).is_err());
+}
+
+#[cfg(feature = "sim")]
+#[test]
+fn sim_hooks() {
+    use async_oneshot::sim::{reset_hooks, set_hooks, Hooks};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    static WAKES: AtomicUsize = AtomicUsize::new(0);
+    static HOOKS: Hooks = Hooks {
+        wake: |waker| {
+            WAKES.fetch_add(1, Ordering::SeqCst);
+            waker.wake_by_ref()
+        },
+        ..Hooks::DEFAULT
+    };
+    set_hooks(&HOOKS);
+    let (mut s, r) = oneshot::<i32>();
+    assert_eq!(
+        block_on(join(r, async { s.send(42).unwrap() })),
+        (Ok(42), ())
+    );
+    reset_hooks();
+    assert!(WAKES.load(Ordering::SeqCst) >= 1);
+}
+
+#[test]
+fn completer() {
+    let (s, r) = oneshot::<i32>();
+    let callback = s.into_completer().into_callback();
+    let caller = std::thread::spawn(move || callback(42));
+    assert_eq!(Ok(42), block_on(r));
+    caller.join().unwrap();
+
+    let (s, r) = oneshot::<i32>();
+    let c = s.into_completer();
+    r.close();
+    assert!(c.is_closed());
+    assert_eq!(Err(Closed()), c.complete(42));
+}
+
+#[test]
+fn closing() {
+    let (s, r) = oneshot::<i32>();
+    let waker = waker_fn(|| ());
+    let mut ctx = Context::from_waker(&waker);
+    let mut closing = Box::pin(s.closing());
+    assert!(closing.as_mut().poll(&mut ctx).is_pending());
+    assert_eq!(Err(Closed()), block_on(r));
+    assert!(closing.as_mut().poll(&mut ctx).is_ready());
+
+    let (mut s, r) = oneshot::<i32>();
+    s.send(42).unwrap();
+    assert_eq!(
+        block_on(join(s.closing(), r)),
+        ((), Ok(42))
+    );
+
+    let (s, r) = oneshot::<i32>();
+    assert_eq!(
+        block_on(join(s.closing(), async { r.close() })),
+        ((), ())
+    );
+}
+
+#[cfg(feature = "handle")]
+#[test]
+fn handle() {
+    use async_oneshot::handle::with_handle;
+    let (task, handle) = with_handle(async { 42 });
+    assert_eq!(block_on(join(task, handle)), ((), Ok(42)));
+
+    let (task, handle) = with_handle(futures::future::pending::<i32>());
+    assert_eq!(
+        block_on(join(task, async { handle.abort() })),
+        ((), ())
+    );
+
+    let (task, handle) = with_handle(async { 42 });
+    drop(task);
+    assert!(handle.is_closed());
+    assert_eq!(Err(Closed()), block_on(handle));
+}
+
+#[cfg(feature = "handle")]
+#[test]
+fn handle_poll_does_not_wake_task() {
+    use async_oneshot::handle::with_handle;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    let wakes = Arc::new(AtomicUsize::new(0));
+    let waker = {
+        let wakes = wakes.clone();
+        waker_fn(move || { wakes.fetch_add(1, Ordering::SeqCst); })
+    };
+    let (mut task, mut handle) = with_handle(futures::future::pending::<i32>());
+    assert!(Pin::new(&mut task).poll(&mut Context::from_waker(&waker)).is_pending());
+    let noop = waker_fn(|| ());
+    for _ in 0..3 {
+        assert!(Pin::new(&mut handle).poll(&mut Context::from_waker(&noop)).is_pending());
+    }
+    assert_eq!(0, wakes.load(Ordering::SeqCst));
+    drop(handle);
+    assert_eq!(1, wakes.load(Ordering::SeqCst));
+}
+
+#[cfg(feature = "priority")]
+#[test]
+fn priority() {
+    let (s, r) = oneshot::<i32>();
+    assert_eq!((0, 0), (s.receiver_priority(), r.sender_priority()));
+    r.set_priority(7);
+    s.set_priority(3);
+    assert_eq!((7, 3), (s.receiver_priority(), r.sender_priority()));
+}
+
+struct ManualTimer {
+    now: core::cell::Cell<u32>,
+    waker: core::cell::RefCell<Option<(u32, core::task::Waker)>>,
+}
+
+impl async_oneshot::timer::Timer for ManualTimer {
+    type Instant = u32;
+    fn now(&self) -> u32 {
+        self.now.get()
+    }
+    fn register_wake_at(&self, deadline: u32, waker: &core::task::Waker) {
+        *self.waker.borrow_mut() = Some((deadline, waker.clone()));
+    }
+}
+
+#[test]
+fn recv_timeout_at() {
+    use async_oneshot::timer::TimedOut;
+    let timer = ManualTimer { now: 0.into(), waker: None.into() };
+    let (mut s, mut r) = oneshot::<i32>();
+    let waker = waker_fn(|| ());
+    let mut ctx = Context::from_waker(&waker);
+    {
+        let mut recv = r.recv_timeout_at(&timer, 10);
+        assert!(Pin::new(&mut recv).poll(&mut ctx).is_pending());
+        assert_eq!(10, timer.waker.borrow().as_ref().unwrap().0);
+        timer.now.set(10);
+        assert_eq!(Poll::Ready(Err(TimedOut())), Pin::new(&mut recv).poll(&mut ctx));
+    }
+    s.send(42).unwrap();
+    assert_eq!(Ok(Ok(42)), block_on(r.recv_timeout_at(&timer, 20)));
+}
+
+#[test]
+fn send_at() {
+    let timer = ManualTimer { now: 0.into(), waker: None.into() };
+    let (s, mut r) = oneshot::<i32>();
+    let waker = waker_fn(|| ());
+    let mut ctx = Context::from_waker(&waker);
+    let mut send = Box::pin(s.send_at(42, &timer, 10));
+    assert_eq!(Poll::Pending, send.as_mut().poll(&mut ctx));
+    assert_eq!(Poll::Pending, Pin::new(&mut r).poll(&mut ctx));
+    timer.now.set(10);
+    assert_eq!(Poll::Ready(Ok(())), send.as_mut().poll(&mut ctx));
+    assert_eq!(Poll::Ready(Ok(42)), Pin::new(&mut r).poll(&mut ctx));
+
+    let (s, r) = oneshot::<i32>();
+    let mut send = Box::pin(s.send_at(42, &timer, 20));
+    assert_eq!(Poll::Pending, send.as_mut().poll(&mut ctx));
+    drop(r);
+    assert_eq!(Poll::Ready(Err(Closed())), send.as_mut().poll(&mut ctx));
+}
+
+#[test]
+fn exchange_values() {
+    let (left, right) = exchange::<i32, &str>();
+    assert_eq!(
+        block_on(join(left.exchange(42), right.exchange("hello"))),
+        (Ok("hello"), Ok(42))
+    );
+
+    let (left, right) = exchange::<i32, &str>();
+    right.close();
+    assert!(left.is_closed());
+    assert_eq!(Err(Closed()), block_on(left.exchange(42)));
+}
+
+#[test]
+fn state_flags() {
+    let (mut s, r) = oneshot::<i32>();
+    assert_eq!(0, s.flags().to_bits());
+    s.send(42).unwrap();
+    assert!(r.flags().contains(flags::VALUE_PRESENT));
+    assert_eq!(Ok(42), block_on(r));
+    let f = s.flags();
+    assert!(f.contains(flags::RECV_DONE));
+    assert!(!f.contains(flags::VALUE_PRESENT));
+    assert_eq!(Some(f), flags::Flags::from_bits(f.to_bits()));
+    assert_eq!(None, flags::Flags::from_bits(!flags::ALL));
+}
+
+#[test]
+fn raw_round_trip() {
+    let (s, r) = oneshot::<i32>();
+    let s = s.into_raw();
+    let r = r.into_raw();
+    let mut s = unsafe { Sender::<i32>::from_raw(s) };
+    s.send(42).unwrap();
+    let s = s.into_raw();
+    let r = unsafe { Receiver::<i32>::from_raw(r) };
+    assert_eq!(Ok(42), block_on(r));
+    // A Sender which has sent does not close the channel when dropped.
+    drop(unsafe { Sender::<i32>::from_raw(s) });
+}
+
+#[cfg(feature = "diagnostics")]
+#[test]
+fn raw_round_trip_resets_counts() {
+    let (_s, mut r) = oneshot::<i32>();
+    let waker = waker_fn(|| ());
+    let mut ctx = Context::from_waker(&waker);
+    assert_eq!(Poll::Pending, Pin::new(&mut r).poll(&mut ctx));
+    assert_eq!(1, r.polls());
+    let r = unsafe { Receiver::<i32>::from_raw(r.into_raw()) };
+    assert_eq!(0, r.polls());
+}
+
+#[test]
+fn wake_after_visible() {
+    use std::sync::{Arc, Mutex};
+
+    // The receiver sees the value as soon as it is woken.
+    let (mut s, r) = oneshot::<i32>();
+    let r = Arc::new(Mutex::new(r));
+    let seen = Arc::new(Mutex::new(None));
+    let waker = {
+        let (r, seen) = (r.clone(), seen.clone());
+        waker_fn(move || {
+            let flags = r.lock().unwrap().flags();
+            *seen.lock().unwrap() = Some(flags.contains(flags::VALUE_PRESENT));
+        })
+    };
+    let mut ctx = Context::from_waker(&waker);
+    assert_eq!(Poll::Pending, Pin::new(&mut *r.lock().unwrap()).poll(&mut ctx));
+    s.send(42).unwrap();
+    assert_eq!(Some(true), *seen.lock().unwrap());
+    assert_eq!(Poll::Ready(Ok(42)), Pin::new(&mut *r.lock().unwrap()).poll(&mut ctx));
+
+    // The receiver sees the channel closed as soon as it is woken.
+    let (s, r) = oneshot::<i32>();
+    let r = Arc::new(Mutex::new(r));
+    let waker = {
+        let (r, seen) = (r.clone(), seen.clone());
+        waker_fn(move || {
+            let flags = r.lock().unwrap().flags();
+            *seen.lock().unwrap() = Some(flags.contains(flags::CLOSED));
+        })
+    };
+    let mut ctx = Context::from_waker(&waker);
+    *seen.lock().unwrap() = None;
+    assert_eq!(Poll::Pending, Pin::new(&mut *r.lock().unwrap()).poll(&mut ctx));
+    drop(s);
+    assert_eq!(Some(true), *seen.lock().unwrap());
+}
+
+#[test]
+fn debug_state() {
+    let (mut s, r) = oneshot::<i32>();
+    assert_eq!(
+        "Sender { state: \"open\", receiver_waiting: false, did_send: false }",
+        format!("{:?}", s)
+    );
+    s.send(42).unwrap();
+    assert_eq!(
+        "Receiver { state: \"full\", sender_waiting: false, did_receive: false }",
+        format!("{:?}", r)
+    );
+    assert_eq!("Flags(VALUE_PRESENT)", format!("{:?}", r.flags()));
+    drop(r);
+    assert_eq!(
+        "Sender { state: \"closed\", receiver_waiting: false, did_send: true }",
+        format!("{:?}", s)
+    );
+}
+
+#[test]
+fn sender_is_waiting() {
+    let (s, r) = oneshot::<i32>();
+    assert!(!r.sender_is_waiting());
+    let mut wait = Box::pin(s.wait());
+    let waker = waker_fn(|| ());
+    let mut ctx = Context::from_waker(&waker);
+    assert!(wait.as_mut().poll(&mut ctx).is_pending());
+    assert!(r.sender_is_waiting());
+    let r = match r.try_recv() {
+        Err(TryRecvError::Empty(r)) => r,
+        other => panic!("{:?}", other),
+    };
+    drop(wait);
+    assert!(r.is_closed());
+
+    // A wait that has finished is no longer waiting, even after it sent.
+    let (mut s, mut r) = oneshot::<i32>();
+    s.send(42).unwrap();
+    let mut wait = Box::pin(s.wait());
+    assert!(wait.as_mut().poll(&mut ctx).is_pending());
+    assert!(r.sender_is_waiting());
+    assert_eq!(Poll::Ready(Ok(42)), Pin::new(&mut r).poll(&mut ctx));
+    let (s, mut r) = oneshot::<i32>();
+    let mut wait = Box::pin(s.wait());
+    assert!(wait.as_mut().poll(&mut ctx).is_pending());
+    assert_eq!(Poll::Pending, Pin::new(&mut r).poll(&mut ctx));
+    assert!(matches!(wait.as_mut().poll(&mut ctx), Poll::Ready(Ok(_))));
+    assert!(!r.sender_is_waiting());
+}
+
+#[test]
+fn sender_closed() {
+    let (mut s, r) = oneshot::<i32>();
+    let waker = waker_fn(|| ());
+    let mut ctx = Context::from_waker(&waker);
+    {
+        let mut closed = Box::pin(s.closed());
+        assert_eq!(Poll::Pending, closed.as_mut().poll(&mut ctx));
+        drop(r);
+        assert_eq!(Poll::Ready(()), closed.as_mut().poll(&mut ctx));
+    }
+
+    let (mut s, r) = oneshot::<i32>();
+    s.send(42).unwrap();
+    assert_eq!(block_on(join(s.closed(), r)), ((), Ok(42)));
+}
+
+#[test]
+fn cancel_receive() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    // Dropping a receiver mid-wait tells a waiting sender straight away.
+    let (s, mut r) = oneshot::<i32>();
+    let wakes = Arc::new(AtomicUsize::new(0));
+    let waker = {
+        let wakes = wakes.clone();
+        waker_fn(move || { wakes.fetch_add(1, Ordering::SeqCst); })
+    };
+    let mut ctx = Context::from_waker(&waker);
+    let mut wait = Box::pin(s.wait());
+    assert!(wait.as_mut().poll(&mut ctx).is_pending());
+    assert_eq!(Poll::Pending, Pin::new(&mut r).poll(&mut ctx));
+    let woken = wakes.load(Ordering::SeqCst);
+    drop(r);
+    assert!(wakes.load(Ordering::SeqCst) > woken);
+    assert!(matches!(wait.as_mut().poll(&mut ctx), Poll::Ready(Err(Closed()))));
+}
+
+#[test]
+fn dropped_closed_deregisters() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    let wakes = Arc::new(AtomicUsize::new(0));
+    let waker = {
+        let wakes = wakes.clone();
+        waker_fn(move || { wakes.fetch_add(1, Ordering::SeqCst); })
+    };
+    let mut ctx = Context::from_waker(&waker);
+
+    // A closed() dropped while pending isn't woken when the receiver finishes.
+    let (mut s, r) = oneshot::<i32>();
+    let mut closed = s.closed();
+    assert!(Pin::new(&mut closed).poll(&mut ctx).is_pending());
+    drop(closed);
+    drop(r);
+    assert_eq!(0, wakes.load(Ordering::SeqCst));
+    assert!(s.is_closed());
+
+    // Nor is a pending closed() whose sender has since sent and dropped.
+    let (mut s, r) = oneshot::<i32>();
+    assert!(Pin::new(&mut s.closed()).poll(&mut ctx).is_pending());
+    s.send(42).unwrap();
+    drop(s);
+    assert_eq!(42, r.try_recv().unwrap());
+    assert_eq!(0, wakes.load(Ordering::SeqCst));
+}
+
+#[test]
+fn selector() {
+    let mut selector = Selector::new();
+    let (mut s1, r1) = oneshot::<i32>();
+    let (s2, r2) = oneshot::<i32>();
+    let (mut s3, r3) = oneshot::<i32>();
+    let id1 = selector.insert(r1);
+    let id2 = selector.insert(r2);
+    let id3 = selector.insert(r3);
+    assert_eq!(3, selector.len());
+
+    let waker = waker_fn(|| ());
+    let mut ctx = Context::from_waker(&waker);
+    assert_eq!(Poll::Pending, selector.poll_next(&mut ctx));
+
+    s3.send(3).unwrap();
+    assert_eq!(Some((id3, Ok(3))), block_on(selector.select()));
+    drop(s2);
+    assert_eq!(Some((id2, Err(Closed()))), block_on(selector.select()));
+    assert!(selector.remove(id1).is_some());
+    assert_eq!(Err(Closed()), s1.send(1));
+    assert_eq!(None, block_on(selector.select()));
+}
+
+#[cfg(feature = "diagnostics")]
+#[test]
+fn selector_ignores_stale_wakes() {
+    let mut selector = Selector::new();
+    let waker = waker_fn(|| ());
+    let mut ctx = Context::from_waker(&waker);
+    let (mut s1, r1) = oneshot::<i32>();
+    let id1 = selector.insert(r1);
+    assert_eq!(Poll::Pending, selector.poll_next(&mut ctx));
+
+    // The removed receiver keeps the waker for its id, which is reused.
+    let r1 = selector.remove(id1).unwrap();
+    let (_s2, r2) = oneshot::<i32>();
+    let id2 = selector.insert(r2);
+    assert_eq!(id1, id2);
+    assert_eq!(Poll::Pending, selector.poll_next(&mut ctx));
+
+    s1.send(1).unwrap();
+    assert_eq!(Poll::Pending, selector.poll_next(&mut ctx));
+    assert_eq!(1, selector.remove(id2).unwrap().polls());
+    assert_eq!(1, r1.try_recv().unwrap());
+}
+
+#[test]
+fn send_to_all_senders() {
+    let (mut s1, r1) = oneshot::<i32>();
+    let (mut s2, r2) = oneshot::<i32>();
+    let (mut s3, r3) = oneshot::<i32>();
+    r2.close();
+    assert_eq!(
+        vec![Ok(()), Err(Closed()), Ok(())],
+        send_to_all([&mut s1, &mut s2, &mut s3], 42)
+    );
+    assert_eq!(Ok(42), block_on(r1));
+    assert_eq!(Ok(42), block_on(r3));
+    assert_eq!(vec![Err(Closed())], send_to_all([&mut s1], 42));
+}
+
+#[test]
+fn notify() {
+    let notify = Notify::new();
+    assert!(!notify.try_notified());
+    notify.notify_one();
+    notify.notify_one();
+    block_on(notify.notified());
+    assert!(!notify.try_notified());
+
+    let waker = waker_fn(|| ());
+    let mut ctx = Context::from_waker(&waker);
+    let mut notified = Box::pin(notify.notified());
+    assert_eq!(Poll::Pending, notified.as_mut().poll(&mut ctx));
+    notify.notify_one();
+    assert_eq!(Poll::Ready(()), notified.as_mut().poll(&mut ctx));
+}
+
+#[test]
+fn notify_wakes_one() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    let notify = Notify::new();
+    let counted = |wakes: &Arc<AtomicUsize>| {
+        let wakes = wakes.clone();
+        waker_fn(move || { wakes.fetch_add(1, Ordering::SeqCst); })
+    };
+    let (first, second, dropped) = (
+        Arc::new(AtomicUsize::new(0)),
+        Arc::new(AtomicUsize::new(0)),
+        Arc::new(AtomicUsize::new(0)),
+    );
+    let mut n1 = Box::pin(notify.notified());
+    let mut n2 = Box::pin(notify.notified());
+    let mut n3 = Box::pin(notify.notified());
+    assert!(n3.as_mut().poll(&mut Context::from_waker(&counted(&dropped))).is_pending());
+    assert!(n1.as_mut().poll(&mut Context::from_waker(&counted(&first))).is_pending());
+    assert!(n2.as_mut().poll(&mut Context::from_waker(&counted(&second))).is_pending());
+
+    // A dropped wait is never woken.
+    drop(n3);
+    notify.notify_one();
+    assert_eq!((0, 1, 0), (dropped.load(Ordering::SeqCst), first.load(Ordering::SeqCst), second.load(Ordering::SeqCst)));
+
+    // A woken wait dropped without taking the permit passes it on.
+    drop(n1);
+    assert_eq!(1, second.load(Ordering::SeqCst));
+    assert!(n2.as_mut().poll(&mut Context::from_waker(&counted(&second))).is_ready());
+    assert!(!notify.try_notified());
+}
+
+#[test]
+fn recv_or_default() {
+    let (mut s, r) = oneshot::<i32>();
+    s.send(42).unwrap();
+    assert_eq!(42, block_on(r.recv_or_default()));
+
+    let (s, r) = oneshot::<i32>();
+    s.close();
+    assert_eq!(0, block_on(r.recv_or_default()));
+
+    let (s, r) = oneshot::<i32>();
+    s.close();
+    assert_eq!(7, block_on(r.recv_or_else(|| 7)));
+}
+
+#[test]
+fn map_input() {
+    let (s, r) = oneshot::<String>();
+    let mut s = s.map_input(|n: i32| n.to_string());
+    assert!(!s.is_closed());
+    s.send(42).unwrap();
+    assert_eq!(Ok("42".to_string()), block_on(r));
+
+    let (s, r) = oneshot::<String>();
+    let mut s = s.map_input(|_: i32| -> String { panic!("should not convert") });
+    r.close();
+    assert_eq!(Err(Closed()), s.send(42));
+}
+
+#[test]
+fn cancel_wait() {
+    let (s, mut r) = oneshot::<i32>();
+    let waker = waker_fn(|| ());
+    let mut ctx = Context::from_waker(&waker);
+    let mut wait = s.wait();
+    assert!(Pin::new(&mut wait).poll(&mut ctx).is_pending());
+    assert!(r.sender_is_waiting());
+    let mut s = wait.cancel().unwrap();
+    assert!(!r.sender_is_waiting());
+
+    // A completed wait removes its waker.
+    let mut wait = s.wait();
+    assert!(Pin::new(&mut wait).poll(&mut ctx).is_pending());
+    assert_eq!(Poll::Pending, Pin::new(&mut r).poll(&mut ctx));
+    s = match Pin::new(&mut wait).poll(&mut ctx) {
+        Poll::Ready(Ok(s)) => s,
+        other => panic!("{:?}", other),
+    };
+    assert!(wait.cancel().is_none());
+    assert!(!s.flags().contains(flags::SEND_PRESENT));
+    s.send(42).unwrap();
+    assert_eq!(Poll::Ready(Ok(42)), Pin::new(&mut r).poll(&mut ctx));
+}
+
+#[cfg(feature = "tracing")]
+#[test]
+fn tracing_wakes() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+    use tracing::field::{Field, Visit};
+    use tracing::{span, subscriber, Event, Metadata, Subscriber};
+
+    /// Counts the spans entered and events logged while it is the
+    /// default subscriber, and collects the labels they carry.
+    #[derive(Clone, Default)]
+    struct Counts {
+        spans: Arc<AtomicUsize>,
+        events: Arc<AtomicUsize>,
+        labels: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl Visit for Counts {
+        fn record_debug(&mut self, field: &Field, value: &dyn core::fmt::Debug) {
+            if field.name() == "label" {
+                self.labels.lock().unwrap().push(format!("{:?}", value));
+            }
+        }
+    }
+
+    impl Subscriber for Counts {
+        fn enabled(&self, _: &Metadata<'_>) -> bool { true }
+        fn new_span(&self, attrs: &span::Attributes<'_>) -> span::Id {
+            attrs.record(&mut self.clone());
+            span::Id::from_u64(1)
+        }
+        fn record(&self, _: &span::Id, _: &span::Record<'_>) {}
+        fn record_follows_from(&self, _: &span::Id, _: &span::Id) {}
+        fn event(&self, event: &Event<'_>) {
+            event.record(&mut self.clone());
+            self.events.fetch_add(1, Ordering::SeqCst);
+        }
+        fn enter(&self, _: &span::Id) { self.spans.fetch_add(1, Ordering::SeqCst); }
+        fn exit(&self, _: &span::Id) {}
+    }
+
+    let counts = Counts::default();
+    subscriber::with_default(counts.clone(), || {
+        let (mut s, mut r) = oneshot::<i32>();
+        let waker = waker_fn(|| ());
+        let mut ctx = Context::from_waker(&waker);
+        assert_eq!(Poll::Pending, Pin::new(&mut r).poll(&mut ctx));
+        s.send(42).unwrap();
+    });
+    assert_eq!(1, counts.events.load(Ordering::SeqCst));
+    assert_eq!(1, counts.spans.load(Ordering::SeqCst));
+    assert!(counts.labels.lock().unwrap().is_empty());
+
+    let counts = Counts::default();
+    subscriber::with_default(counts.clone(), || {
+        let (mut s, mut r) = oneshot_labelled::<i32>("jobs");
+        let waker = waker_fn(|| ());
+        let mut ctx = Context::from_waker(&waker);
+        assert_eq!(Poll::Pending, Pin::new(&mut r).poll(&mut ctx));
+        s.send(42).unwrap();
+    });
+    assert_eq!(vec!["\"jobs\""; 2], *counts.labels.lock().unwrap());
+}
+
+#[test]
+fn completion_map() {
+    let mut map = CompletionMap::new();
+    let r1 = map.insert(1);
+    let r2 = map.insert(2);
+    let r3 = map.insert(3);
+    assert_eq!(3, map.len());
+
+    map.complete(&1, "one").unwrap();
+    assert_eq!(Ok("one"), block_on(r1));
+    assert_eq!(Err(Closed()), map.complete(&1, "again"));
+
+    assert!(map.cancel(&2));
+    assert_eq!(Err(Closed()), block_on(r2));
+
+    drop(r3);
+    assert!(map.contains_key(&3));
+    map.prune();
+    assert!(map.is_empty());
+
+    // Dropped receivers are forgotten as the map grows, without pruning.
+    for key in 0..1000 {
+        drop(map.insert(key));
+        map.complete(&key, "late").unwrap_err();
+        drop(map.insert(key + 1000));
+    }
+    assert!(map.len() <= 32, "{} keys left", map.len());
+}
+
+#[test]
+fn spin_before_waiting() {
+    let (mut s, mut r) = oneshot::<i32>();
+    r.set_spins(100);
+    let waker = waker_fn(|| ());
+    let mut ctx = Context::from_waker(&waker);
+    assert_eq!(Poll::Pending, Pin::new(&mut r).poll(&mut ctx));
+    s.send(42).unwrap();
+    assert_eq!(Poll::Ready(Ok(42)), Pin::new(&mut r).poll(&mut ctx));
+
+    let (mut s, mut r) = oneshot::<i32>();
+    r.set_spins(10_000);
+    let sender = std::thread::spawn(move || s.send(42));
+    assert_eq!(Ok(42), block_on(r));
+    sender.join().unwrap().unwrap();
+}
+
+#[test]
+fn transfer_waiting() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    let wakes = Arc::new(AtomicUsize::new(0));
+    let waker = {
+        let wakes = wakes.clone();
+        waker_fn(move || { wakes.fetch_add(1, Ordering::SeqCst); })
+    };
+    let mut ctx = Context::from_waker(&waker);
+    let (mut old_s, mut old_r) = oneshot::<i32>();
+    let (mut new_s, mut new_r) = oneshot::<&str>();
+    assert!(!old_r.transfer_waiting_to(&mut new_r));
+    assert_eq!(Poll::Pending, Pin::new(&mut old_r).poll(&mut ctx));
+    assert!(old_r.transfer_waiting_to(&mut new_r));
+
+    old_s.send(1).unwrap();
+    assert_eq!(0, wakes.load(Ordering::SeqCst));
+    new_s.send("moved").unwrap();
+    assert_eq!(1, wakes.load(Ordering::SeqCst));
+    assert_eq!(Ok("moved"), block_on(new_r));
+}
+
+#[test]
+fn forgotten_handles() {
+    let (s, r) = oneshot::<i32>();
+    assert_eq!(1, r.peer_handle_count());
+    assert_eq!(1, s.peer_handle_count());
+    // A forgotten sender never closes the channel; only the handle count
+    // reveals that it is still referenced. into_raw forgets it without
+    // leaking for good.
+    let s = s.into_raw();
+    assert!(!r.is_closed());
+    assert_eq!(1, r.peer_handle_count());
+    let r = match r.try_recv() {
+        Err(TryRecvError::Empty(r)) => r,
+        other => panic!("{:?}", other),
+    };
+
+    drop(unsafe { Sender::<i32>::from_raw(s) });
+    assert!(r.is_closed());
+
+    let (s, r) = oneshot::<i32>();
+    drop(r);
+    assert_eq!(0, s.peer_handle_count());
+}
+
+#[test]
+fn with_value() {
+    let (mut s, mut r) = oneshot::<String>();
+    assert_eq!(None, r.with_value(|v| v.len()));
+    s.send("hello".to_string()).unwrap();
+    assert_eq!(Some(5), r.with_value(|v| v.len()));
+    assert_eq!(Ok("hello".to_string()), r.try_recv().map_err(|_| ()));
+}
+
+#[test]
+fn close_wakes_sender() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    let wakes = Arc::new(AtomicUsize::new(0));
+    let waker = {
+        let wakes = wakes.clone();
+        waker_fn(move || { wakes.fetch_add(1, Ordering::SeqCst); })
+    };
+    let mut ctx = Context::from_waker(&waker);
+
+    // Dropping the receiver wakes a sender waiting for it to go away.
+    let (mut s, r) = oneshot::<i32>();
+    let mut closed = Box::pin(s.closed());
+    assert_eq!(Poll::Pending, closed.as_mut().poll(&mut ctx));
+    drop(r);
+    assert_eq!(1, wakes.load(Ordering::SeqCst));
+    assert_eq!(Poll::Ready(()), closed.as_mut().poll(&mut ctx));
+
+    // And a sender waiting for the receiver to be waiting.
+    let (s, r) = oneshot::<i32>();
+    let mut wait = s.wait();
+    assert!(Pin::new(&mut wait).poll(&mut ctx).is_pending());
+    drop(r);
+    assert_eq!(2, wakes.load(Ordering::SeqCst));
+    assert!(matches!(Pin::new(&mut wait).poll(&mut ctx), Poll::Ready(Err(Closed()))));
+}
+
+#[cfg(feature = "parking")]
+#[test]
+fn parking_block_on() {
+    let (left, right) = exchange::<i32, &str>();
+    let other = std::thread::spawn(move || async_oneshot::block_on(right.exchange("hello")));
+    assert_eq!(Ok("hello"), async_oneshot::block_on(left.exchange(42)));
+    assert_eq!(Ok(42), other.join().unwrap());
+
+    let (mut s, r) = oneshot::<i32>();
+    let receiver = std::thread::spawn(move || r.recv_blocking());
+    s.send(42).unwrap();
+    async_oneshot::block_on(s.closed());
+    assert_eq!(Ok(42), receiver.join().unwrap());
+}
+
+#[cfg(feature = "observer")]
+#[test]
+fn observer() {
+    let waker = waker_fn(|| ());
+    let mut ctx = Context::from_waker(&waker);
+
+    let (mut s, r) = oneshot::<i32>();
+    let observer = r.observe();
+    assert!(!observer.is_finished());
+    assert_eq!(Some(0), observer.flags().map(|f| f.to_bits()));
+    let mut finished = Box::pin(observer.finished());
+    assert_eq!(Poll::Pending, finished.as_mut().poll(&mut ctx));
+    s.send(42).unwrap();
+    assert_eq!(Poll::Pending, finished.as_mut().poll(&mut ctx));
+    assert_eq!(1, s.peer_handle_count());
+    assert_eq!(Ok(42), block_on(r));
+    assert_eq!(Poll::Ready(()), finished.as_mut().poll(&mut ctx));
+
+    let (s, r) = oneshot::<i32>();
+    let observer = s.observe().clone();
+    drop(s);
+    assert!(observer.is_finished());
+    drop(r);
+    assert_eq!(None, observer.flags());
+    block_on(observer.finished());
+
+    // A dropped wait removes its waker.
+    let (_s, r) = oneshot::<i32>();
+    let observer = r.observe();
+    let mut finished = Box::pin(observer.finished());
+    assert_eq!(Poll::Pending, finished.as_mut().poll(&mut ctx));
+    assert!(r.flags().contains(flags::OBSERVERS_PRESENT));
+    drop(finished);
+    assert!(!r.flags().contains(flags::OBSERVERS_PRESENT));
+}
+
+#[cfg(feature = "repr-c")]
+#[test]
+fn repr_c_layout() {
+    let (s, r) = oneshot::<i32>();
+    let (other, _r) = oneshot::<i32>();
+    // Both handles start with a pointer to the same shared state, which
+    // is what into_raw hands out.
+    let first = |p: *const ()| unsafe { *(p as *const *const ()) };
+    let sender = first(&s as *const Sender<i32> as *const ());
+    let receiver = first(&r as *const Receiver<i32> as *const ());
+    assert_eq!(sender, receiver);
+    assert_ne!(sender, first(&other as *const Sender<i32> as *const ()));
+    let raw = s.into_raw();
+    assert_eq!(sender, raw);
+    drop(unsafe { Sender::<i32>::from_raw(raw) });
+}
+
+#[test]
+fn tagged_closed() {
+    let (s, r) = oneshot::<i32>();
+    s.close();
+    let err = block_on(r.recv_tagged("config")).unwrap_err();
+    assert_eq!(TaggedClosed("config"), err);
+    assert_eq!("channel config closed", err.to_string());
+    assert_eq!(Closed(), Closed::from(err));
+
+    let (mut s, r) = oneshot::<i32>();
+    r.close();
+    assert_eq!(Err(TaggedClosed(7)), s.send(42).map_err(|e| e.with_tag(7)));
+}
+
+#[test]
+fn close_pair_handles() {
+    let (mut s, r) = oneshot::<i32>();
+    s.send(42).unwrap();
+    let (out, peer) = oneshot::<&str>();
+    assert_eq!(Some(42), close_pair(out, r));
+    assert_eq!(Err(Closed()), block_on(peer));
+
+    let (s, r) = oneshot::<i32>();
+    assert_eq!(None, close_pair(s, r));
+}
+
+#[test]
+fn try_send_salvage() {
+    let (mut s, r) = oneshot::<i32>();
+    drop(r);
+    let mut dead_letters = Vec::new();
+    let err = s.try_send(42).map_err(|e| e.salvage(|v| dead_letters.push(v)));
+    assert_eq!(Err(Closed()), err);
+    assert_eq!(vec![42], dead_letters);
+
+    let (mut s, r) = oneshot::<i32>();
+    s.try_send(42).unwrap();
+    assert_eq!(43, s.try_send(43).unwrap_err().into_inner());
+    assert_eq!(Ok(42), block_on(r));
+}
+
+#[test]
+fn pipe_relays() {
+    let (mut s1, r1) = oneshot::<i32>();
+    let (s2, r2) = oneshot::<i32>();
+    s1.send(42).unwrap();
+    assert_eq!(Ok(()), block_on(pipe(r1, s2)));
+    assert_eq!(Ok(42), block_on(r2));
+
+    // The upstream Sender dropping closes the downstream channel.
+    let (s1, r1) = oneshot::<i32>();
+    let (s2, r2) = oneshot::<i32>();
+    drop(s1);
+    assert_eq!(Err(Closed()), block_on(pipe(r1, s2)));
+    assert_eq!(Err(Closed()), block_on(r2));
+
+    // The downstream Receiver dropping closes the upstream channel.
+    let (s1, r1) = oneshot::<i32>();
+    let (s2, r2) = oneshot::<i32>();
+    let relay = pipe(r1, s2);
+    let mut upstream = s1;
+    let (result, ()) = block_on(join(relay, async move {
+        drop(r2);
+        upstream.closed().await;
+    }));
+    assert_eq!(Err(Closed()), result);
+}